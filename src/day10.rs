@@ -1,8 +1,7 @@
-use std::collections::{HashMap, HashSet};
-
 use crate::util::{
     grid::Grid,
     position::{Direction, Position},
+    topo::{topological_sort, Cycle},
 };
 
 type Input = Grid<Height>;
@@ -29,90 +28,285 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u32 {
-    let mut pos_to_9s = HashMap::new();
-
-    input
+    hiking_trails(input)
+        .counts()
         .iter()
-        .map(|(position, height)| {
-            if height == Height(0) {
-                dfs_unique(&mut pos_to_9s, input, position, height).len() as u32
-            } else {
-                0
-            }
-        })
-        .sum()
+        .map(|c| c.endpoints)
+        .sum::<usize>() as u32
 }
 
 pub fn part2(input: &Input) -> u32 {
-    let mut pos_to_trails = HashMap::new();
-
-    input
+    hiking_trails(input)
+        .counts()
         .iter()
-        .map(|(position, height)| {
-            if height == Height(0) {
-                dfs_count(&mut pos_to_trails, input, position, height)
-            } else {
-                0
-            }
-        })
-        .sum()
+        .map(|c| c.paths)
+        .sum::<u64>() as u32
+}
+
+fn hiking_trails(map: &Grid<Height>) -> TrailGraph {
+    TrailGraph::new(
+        map,
+        |_, h| h == Height(0),
+        |_, h| h == Height(9),
+        |from, to| to == from + 1,
+    )
+    .expect("uphill steps can't loop")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrailCounts {
+    pub start: Position,
+    /// Distinct ends reachable from `start`.
+    pub endpoints: usize,
+    /// Distinct paths from `start` to any end.
+    pub paths: u64,
+}
+
+/// The steps allowed between neighbouring cells of a height map.
+///
+/// Steps can go any way `can_step` likes as long as they never lead round in a loop,
+/// so the cells have a topological order and everything can be counted in one pass
+/// from the far end of it.
+pub struct TrailGraph {
+    n_cols: i32,
+    starts: Vec<usize>,
+    is_end: Vec<bool>,
+    successors: Vec<Vec<usize>>,
+    /// Every cell after everything it can step to.
+    order: Vec<usize>,
 }
 
-pub fn dfs_unique(
-    pos_to_9s: &mut HashMap<Position, HashSet<Position>>,
-    map: &Grid<Height>,
-    position: Position,
-    height: Height,
-) -> HashSet<Position> {
-    let mut result = HashSet::new();
+impl TrailGraph {
+    /// Fails with the loop if the steps `can_step` allows lead round in one,
+    /// since then there'd be infinitely many paths.
+    pub fn new(
+        map: &Grid<Height>,
+        is_start: impl Fn(Position, Height) -> bool,
+        is_end: impl Fn(Position, Height) -> bool,
+        can_step: impl Fn(Height, Height) -> bool,
+    ) -> Result<Self, Cycle<Position>> {
+        let mut graph = TrailGraph {
+            n_cols: map.n_cols(),
+            starts: Vec::new(),
+            is_end: Vec::new(),
+            successors: Vec::new(),
+            order: Vec::new(),
+        };
 
-    if let Some(n_trails) = pos_to_9s.get(&position) {
-        return n_trails.clone();
+        // Grid iteration is row-major, same as `index`.
+        for (pos, height) in map.iter() {
+            let i = graph.is_end.len();
+            if is_start(pos, height) {
+                graph.starts.push(i);
+            }
+            graph.is_end.push(is_end(pos, height));
+
+            let successors = Direction::ALL
+                .into_iter()
+                .map(|d| pos.moved_in(d))
+                .filter(|next| map.get(*next).is_some_and(|h| can_step(height, *h)))
+                .map(|next| graph.index(next))
+                .collect();
+            graph.successors.push(successors);
+        }
+
+        let cells = (0..graph.is_end.len()).collect::<Vec<_>>();
+        let steps = graph
+            .successors
+            .iter()
+            .enumerate()
+            .flat_map(|(i, next)| next.iter().map(move |j| (i, *j)));
+        graph.order = topological_sort(&cells, steps).map_err(|Cycle(cycle)| {
+            Cycle(cycle.into_iter().map(|i| graph.position(i)).collect())
+        })?;
+        graph.order.reverse();
+
+        Ok(graph)
     }
 
-    for d in Direction::ALL {
-        let position = position.moved_in(d);
+    fn index(&self, pos: Position) -> usize {
+        (pos.row * self.n_cols + pos.col) as usize
+    }
 
-        if let Some(h2) = map.get(position) {
-            if *h2 == height + 1 {
-                if *h2 == Height(9) {
-                    result.insert(position);
-                } else {
-                    result.extend(dfs_unique(pos_to_9s, map, position, *h2));
+    fn position(&self, i: usize) -> Position {
+        Position::new(i as i32 / self.n_cols, i as i32 % self.n_cols)
+    }
+
+    /// Counts for each start, in row-major order.
+    pub fn counts(&self) -> Vec<TrailCounts> {
+        // Reachable ends as a bitset per cell, ends numbered in row-major order.
+        let mut n_ends = 0;
+        let end_ids = self
+            .is_end
+            .iter()
+            .map(|is_end| {
+                n_ends += *is_end as usize;
+                n_ends.saturating_sub(1)
+            })
+            .collect::<Vec<_>>();
+        let words = n_ends.div_ceil(64);
+
+        let mut reachable = vec![vec![0u64; words]; self.is_end.len()];
+        let mut paths = vec![0u64; self.is_end.len()];
+
+        for &i in &self.order {
+            let mut ends = vec![0u64; words];
+            let mut n_paths = 0;
+            if self.is_end[i] {
+                ends[end_ids[i] / 64] |= 1 << (end_ids[i] % 64);
+                n_paths += 1;
+            }
+            for &j in &self.successors[i] {
+                for (a, b) in ends.iter_mut().zip(&reachable[j]) {
+                    *a |= b;
                 }
+                n_paths += paths[j];
             }
+
+            reachable[i] = ends;
+            paths[i] = n_paths;
         }
+
+        self.starts
+            .iter()
+            .map(|i| TrailCounts {
+                start: self.position(*i),
+                endpoints: reachable[*i].iter().map(|w| w.count_ones() as usize).sum(),
+                paths: paths[*i],
+            })
+            .collect()
     }
 
-    pos_to_9s.insert(position, result.clone());
-    result
+    /// Every path from `start` to an end, which there can be exponentially many of.
+    #[allow(dead_code)]
+    pub fn paths_from(&self, start: Position) -> Vec<Vec<Position>> {
+        let mut paths = Vec::new();
+        let mut path = vec![self.index(start)];
+        // (cell, next successor to try)
+        let mut stack = vec![(self.index(start), 0)];
+
+        if self.is_end[path[0]] {
+            paths.push(vec![start]);
+        }
+        while let Some((i, next)) = stack.last_mut() {
+            let Some(&j) = self.successors[*i].get(*next) else {
+                stack.pop();
+                path.pop();
+                continue;
+            };
+            *next += 1;
+
+            path.push(j);
+            stack.push((j, 0));
+            if self.is_end[j] {
+                paths.push(path.iter().map(|i| self.position(*i)).collect());
+            }
+        }
+
+        paths
+    }
 }
 
-pub fn dfs_count(
-    pos_to_trails: &mut HashMap<Position, u32>,
-    map: &Grid<Height>,
-    position: Position,
-    height: Height,
-) -> u32 {
-    if let Some(n_trails) = pos_to_trails.get(&position) {
-        return *n_trails;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 36);
     }
 
-    let mut result = 0;
-    for d in Direction::ALL {
-        let position = position.moved_in(d);
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 81);
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = hiking_trails(&parse(EXAMPLE)).counts();
+        assert_eq!(counts.len(), 9);
+        assert_eq!(
+            counts[0],
+            TrailCounts {
+                start: Position::new(0, 2),
+                endpoints: 5,
+                paths: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let map = parse(EXAMPLE);
+        let graph = hiking_trails(&map);
 
-        if let Some(h2) = map.get(position) {
-            if *h2 == height + 1 {
-                if *h2 == Height(9) {
-                    result += 1;
-                } else {
-                    result += dfs_count(pos_to_trails, map, position, *h2);
+        for counts in graph.counts() {
+            let paths = graph.paths_from(counts.start);
+            assert_eq!(paths.len() as u64, counts.paths);
+            for path in paths {
+                assert_eq!(path.len(), 10);
+                for (i, p) in path.iter().enumerate() {
+                    assert!(map.is(*p, Height(i as u8)));
                 }
             }
         }
     }
 
-    pos_to_trails.insert(position, result.clone());
-    result
+    #[test]
+    fn test_custom_predicates() {
+        let map = parse("0123\n1234\n8765\n9876");
+
+        // Any climb of up to 2, ending anywhere at 7 or above.
+        let graph = TrailGraph::new(
+            &map,
+            |p, _| p == Position::new(0, 0),
+            |_, h| h.0 >= 7,
+            |from, to| to.0 > from.0 && to.0 - from.0 <= 2,
+        )
+        .unwrap();
+        let counts = graph.counts();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0].endpoints, 5);
+        assert_eq!(
+            graph.paths_from(counts[0].start).len() as u64,
+            counts[0].paths
+        );
+
+        // Downhill works just as well, the same trails walked the other way.
+        let map = parse(EXAMPLE);
+        let graph = TrailGraph::new(
+            &map,
+            |_, h| h == Height(9),
+            |_, h| h == Height(0),
+            |from, to| from == to + 1,
+        )
+        .unwrap();
+        let paths = graph.counts().iter().map(|c| c.paths).sum::<u64>();
+        assert_eq!(paths, 81);
+    }
+
+    #[test]
+    fn test_loops() {
+        // Level ground can be walked back and forth forever.
+        let map = parse("012\n344");
+        let result = TrailGraph::new(&map, |_, _| true, |_, _| true, |from, to| to >= from);
+        let Err(Cycle(cycle)) = result else {
+            panic!("expected a loop");
+        };
+        assert_eq!(cycle.len(), 2);
+        assert!(cycle.contains(&Position::new(1, 1)) && cycle.contains(&Position::new(1, 2)));
+
+        // Without the level steps there is no way back.
+        let uphill = TrailGraph::new(&map, |_, _| true, |_, _| true, |from, to| to > from);
+        assert!(uphill.is_ok());
+    }
 }
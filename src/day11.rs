@@ -1,4 +1,9 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use crate::util::memo::Memo;
 
 type Input = Vec<u64>;
 
@@ -10,62 +15,320 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u64 {
-    let mut stones = input.clone();
-
-    for _ in 0..25 {
-        let mut new_stones = Vec::with_capacity(stones.len());
-
-        for s in stones {
-            if s == 0 {
-                new_stones.push(1);
-            } else if let Some((s1, s2)) = split(s) {
-                new_stones.push(s1);
-                new_stones.push(s2);
-            } else {
-                new_stones.push(s * 2024);
-            }
-        }
+    total(input, 25)
+}
 
-        stones = new_stones;
-    }
+pub fn part2(input: &Input) -> u64 {
+    total(input, 75)
+}
 
-    stones.len() as u64
+fn total(stones: &[u64], blinks: u32) -> u64 {
+    let mut blinker = Blinker::new(Rule::STANDARD.to_vec());
+    stones
+        .iter()
+        .map(|s| blinker.count(*s, blinks).unwrap())
+        .sum::<u128>()
+        .try_into()
+        .unwrap()
 }
 
-pub fn part2(input: &Input) -> u64 {
-    let mut stones: HashMap<u64, u64> = HashMap::new();
-    for s in input {
-        *stones.entry(*s).or_default() += 1;
-    }
-
-    for _ in 0..75 {
-        let mut new_stones = HashMap::with_capacity(stones.len());
-
-        for (s, count) in stones {
-            if s == 0 {
-                *new_stones.entry(1).or_default() += count;
-            } else if let Some((s1, s2)) = split(s) {
-                *new_stones.entry(s1).or_default() += count;
-                *new_stones.entry(s2).or_default() += count;
-            } else {
-                *new_stones.entry(s * 2024).or_default() += count;
+/// Prints how many stones there are after `blinks` blinks and how they're spread out,
+/// for `day11-blinks`.
+pub fn print_blinks(stones: &Input, blinks: u32) {
+    const M: u64 = 1_000_000_007;
+    let mut blinker = Blinker::new(Rule::STANDARD.to_vec());
+
+    let exact = stones.iter().try_fold(0u128, |total, s| {
+        total.checked_add(blinker.count(*s, blinks)?)
+    });
+    match exact {
+        Some(n) => println!("{} stones after {} blinks", n, blinks),
+        None => {
+            // Every stone has a rule, so only the exact count can fail.
+            let n = stones
+                .iter()
+                .map(|s| blinker.count_mod(*s, blinks, M).unwrap())
+                .fold(0, |total, n| (total + n) % M);
+            println!("{} stones after {} blinks, modulo {}", n, blinks, M);
+        }
+    }
+
+    match blinker.distribution(stones, blinks) {
+        Some(distribution) => {
+            let mut common = distribution.into_iter().collect::<Vec<_>>();
+            common.sort_by_key(|(stone, n)| (Reverse(*n), *stone));
+            println!("{} distinct values, the most common:", common.len());
+            for (stone, n) in common.iter().take(10) {
+                println!("{:>20} x{}", stone, n);
             }
         }
+        None => println!("too many of each value to count exactly"),
+    }
 
-        stones = new_stones;
+    match blinker.saturation(stones, blinks) {
+        Some((blink, n)) => println!("no new values after blink {}, {} in all", blink, n),
+        None => println!("still turning up new values after {} blinks", blinks),
     }
+}
 
-    stones.into_iter().map(|(_, count)| count).sum()
+/// What happens to a stone on a blink. The first rule in a list that applies wins.
+#[derive(Clone, Copy, Debug)]
+pub enum Rule {
+    /// A stone engraved with the first number becomes one engraved with the second.
+    Replace(u64, u64),
+    /// A stone with an even number of digits splits into its left and right halves.
+    SplitEvenDigits,
+    /// The stone's number is multiplied, unless that would overflow.
+    Multiply(u64),
+    /// Anything else, `None` if it doesn't apply.
+    #[allow(dead_code)]
+    Custom(fn(u64) -> Option<Vec<u64>>),
+}
+
+impl Rule {
+    pub const STANDARD: [Rule; 3] = [
+        Rule::Replace(0, 1),
+        Rule::SplitEvenDigits,
+        Rule::Multiply(2024),
+    ];
+
+    pub fn apply(&self, stone: u64) -> Option<Vec<u64>> {
+        match self {
+            Rule::Replace(from, to) => (stone == *from).then(|| vec![*to]),
+            Rule::SplitEvenDigits => split(stone).map(|(a, b)| vec![a, b]),
+            Rule::Multiply(n) => stone.checked_mul(*n).map(|s| vec![s]),
+            Rule::Custom(f) => f(stone),
+        }
+    }
 }
 
 fn split(stone: u64) -> Option<(u64, u64)> {
-    let n_digits = stone.ilog10() + 1;
+    let n_digits = stone.checked_ilog10().unwrap_or(0) + 1;
 
     if n_digits % 2 == 1 {
         return None;
     }
 
-    let d = 10u64.pow(n_digits / 2) as u64;
+    let d = 10u64.pow(n_digits / 2);
     let s1 = stone / d;
     Some((s1, stone - (s1 * d)))
 }
+
+/// Applies a list of rules to stones, remembering what it's worked out.
+pub struct Blinker {
+    rules: Vec<Rule>,
+    /// What each stone turns into on one blink, `None` if no rule applies.
+    children: HashMap<u64, Option<Vec<u64>>>,
+    /// Keyed by the modulus too, `None` being the exact count.
+    counts: Memo<(u64, u32, Option<u64>), Option<u128>>,
+}
+
+impl Blinker {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Blinker {
+            rules,
+            children: HashMap::new(),
+            counts: Memo::new(),
+        }
+    }
+
+    fn children(&mut self, stone: u64) -> Option<Vec<u64>> {
+        let rules = &self.rules;
+        self.children
+            .entry(stone)
+            .or_insert_with(|| rules.iter().find_map(|r| r.apply(stone)))
+            .clone()
+    }
+
+    /// Number of stones `stone` turns into after `blinks` blinks.
+    ///
+    /// `None` if that doesn't fit in a `u128`, which happens after a couple of hundred
+    /// blinks, or a stone turns up that no rule applies to. `count_mod` goes further.
+    pub fn count(&mut self, stone: u64, blinks: u32) -> Option<u128> {
+        self.count_with(stone, blinks, None)
+    }
+
+    /// `count` modulo `m`, which never overflows, so thousands of blinks are fine.
+    /// `None` only if a stone turns up that no rule applies to.
+    pub fn count_mod(&mut self, stone: u64, blinks: u32, m: u64) -> Option<u64> {
+        assert!(m > 0, "modulus must be positive");
+        self.count_with(stone, blinks, Some(m)).map(|n| n as u64)
+    }
+
+    // Works through an explicit stack rather than recursing, so deep blinks don't blow the stack.
+    fn count_with(&mut self, stone: u64, blinks: u32, modulus: Option<u64>) -> Option<u128> {
+        // Both sides are below a `u64` modulus, so only the exact count can overflow.
+        let add = |total: u128, n: u128| match modulus {
+            Some(m) => Some((total + n) % m as u128),
+            None => total.checked_add(n),
+        };
+        let one = modulus.map_or(1, |m| 1 % m as u128);
+
+        let mut stack = vec![(stone, blinks)];
+        while let Some(&(stone, blinks)) = stack.last() {
+            if blinks == 0 || self.counts.contains(&(stone, blinks, modulus)) {
+                stack.pop();
+                continue;
+            }
+
+            let Some(children) = self.children(stone) else {
+                self.counts.insert((stone, blinks, modulus), None);
+                stack.pop();
+                continue;
+            };
+
+            // Children first, then come back to this one.
+            let mut total = Some(0u128);
+            let mut ready = true;
+            for c in children {
+                let n = if blinks == 1 {
+                    Some(one)
+                } else if self.counts.contains(&(c, blinks - 1, modulus)) {
+                    self.counts.get(&(c, blinks - 1, modulus)).unwrap()
+                } else {
+                    stack.push((c, blinks - 1));
+                    ready = false;
+                    continue;
+                };
+                total = total.zip(n).and_then(|(t, n)| add(t, n));
+            }
+
+            if ready {
+                self.counts.insert((stone, blinks, modulus), total);
+                stack.pop();
+            }
+        }
+
+        if blinks == 0 {
+            Some(one)
+        } else {
+            self.counts.get(&(stone, blinks, modulus)).flatten()
+        }
+    }
+}
+
+// For looking into how the stones behave, the parts only need counts.
+impl Blinker {
+    /// How many of each stone there are after `blinks` blinks.
+    /// `None` under the same conditions as `count`.
+    pub fn distribution(&mut self, stones: &[u64], blinks: u32) -> Option<HashMap<u64, u128>> {
+        let mut stones = stones.iter().fold(HashMap::new(), |mut m, s| {
+            *m.entry(*s).or_insert(0) += 1;
+            m
+        });
+
+        for _ in 0..blinks {
+            let mut next = HashMap::new();
+            for (stone, n) in stones {
+                for c in self.children(stone)? {
+                    let total = next.entry(c).or_insert(0u128);
+                    *total = total.checked_add(n)?;
+                }
+            }
+            stones = next;
+        }
+
+        Some(stones)
+    }
+
+    /// The first blink that doesn't turn up a stone value that hadn't been seen before,
+    /// along with how many values were seen. From then on the set can't grow any more.
+    /// `None` if it's still growing after `max_blinks`, or a stone has no rule.
+    pub fn saturation(&mut self, stones: &[u64], max_blinks: u32) -> Option<(u32, usize)> {
+        let mut seen = stones.iter().copied().collect::<HashSet<_>>();
+        let mut current = seen.clone();
+
+        for blink in 1..=max_blinks {
+            let mut next = HashSet::new();
+            for stone in current {
+                next.extend(self.children(stone)?);
+            }
+
+            let n_seen = seen.len();
+            seen.extend(next.iter().copied());
+            if seen.len() == n_seen {
+                return Some((blink, n_seen));
+            }
+            current = next;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse("125 17")), 55312);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let mut blinker = Blinker::new(Rule::STANDARD.to_vec());
+
+        // 125 17 -> 253000 1 7 -> 253 0 2024 14168 -> 512072 1 20 24 28676032
+        let distribution = blinker.distribution(&[125, 17], 3).unwrap();
+        let mut stones = distribution.keys().copied().collect::<Vec<_>>();
+        stones.sort();
+        assert_eq!(stones, vec![1, 20, 24, 512072, 28676032]);
+
+        let after_25 = blinker.distribution(&[125, 17], 25).unwrap();
+        assert_eq!(after_25.values().sum::<u128>(), 55312);
+        assert_eq!(
+            blinker.count(125, 25).unwrap() + blinker.count(17, 25).unwrap(),
+            55312
+        );
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        // Stones halve if even, otherwise split in two copies of the next number down.
+        let rules = vec![
+            Rule::Replace(0, 1),
+            Rule::Custom(|s| (s % 2 == 0).then(|| vec![s / 2])),
+            Rule::Custom(|s| Some(vec![s - 1, s - 1])),
+        ];
+        let mut blinker = Blinker::new(rules);
+
+        // 3 -> 2 2 -> 1 1 -> 0 0 0 0 -> 1 1 1 1
+        assert_eq!(blinker.count(3, 4), Some(4));
+        assert_eq!(blinker.count(3, 5), Some(8));
+        assert_eq!(blinker.saturation(&[3], 100), Some((4, 4)));
+
+        // Nothing applies to 5.
+        let mut blinker = Blinker::new(vec![Rule::Replace(3, 5)]);
+        assert_eq!(blinker.count(3, 1), Some(1));
+        assert_eq!(blinker.count(3, 2), None);
+        assert_eq!(blinker.distribution(&[3], 2), None);
+    }
+
+    #[test]
+    fn test_many_blinks() {
+        let mut blinker = Blinker::new(Rule::STANDARD.to_vec());
+
+        // Way past anything that fits in a u128, but mustn't blow the stack.
+        assert_eq!(blinker.count(0, 2000), None);
+        let exact = blinker.count(0, 150).unwrap();
+
+        // Modular counts keep going, and agree with the exact ones where there are any.
+        const M: u64 = 1_000_000_007;
+        assert_eq!(
+            blinker.count_mod(0, 150, M),
+            Some((exact % M as u128) as u64)
+        );
+        assert!(blinker.count_mod(0, 5000, M).is_some());
+        assert_eq!(blinker.count_mod(0, 5000, 1), Some(0));
+        assert_eq!(blinker.count_mod(7, 0, M), Some(1));
+
+        // Everything reachable from 0 shows up fairly quickly.
+        let (blink, n_values) = blinker.saturation(&[0], 1000).unwrap();
+        assert!(blink < 100);
+        assert_eq!(
+            blinker.distribution(&[0], blink + 5).unwrap().len(),
+            n_values
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::util::{
+    components::{self, Connectivity},
     grid::Grid,
     position::{Direction, Position},
 };
@@ -12,170 +13,391 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u64 {
-    Region::find_all(input)
-        .iter()
-        .map(|r| r.area() * r.perimiter())
-        .sum()
+    Garden::new(input).pricing().values().map(|p| p.price).sum()
 }
 
 pub fn part2(input: &Input) -> u64 {
-    Region::find_all(input)
-        .iter()
-        .map(|r| r.area() * r.corners())
+    Garden::new(input)
+        .pricing()
+        .values()
+        .map(|p| p.bulk_price)
         .sum()
 }
 
-struct Region {
-    points: HashSet<Position>,
+/// A closed loop of fence, as the lattice points between cells where it turns.
+/// Point `(row, col)` is the top-left corner of cell `(row, col)`.
+///
+/// Loops go clockwise around a region's outside and anticlockwise around its holes,
+/// so the region is always on the right.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Polygon {
+    pub vertices: Vec<Position>,
 }
 
-impl Region {
-    fn find_all(grid: &Input) -> Vec<Region> {
-        let mut regions = Vec::new();
-        let mut seen: HashSet<Position> = HashSet::new();
+impl Polygon {
+    /// Every turn starts a new side.
+    pub fn sides(&self) -> u64 {
+        self.vertices.len() as u64
+    }
 
-        for (point, _) in grid.iter() {
-            if seen.contains(&point) {
-                continue;
-            }
+    pub fn perimeter(&self) -> u64 {
+        self.edges().map(|(a, b)| a.distance_to(&b) as u64).sum()
+    }
 
-            let points = flood_fill(grid, point);
+    /// Positive for clockwise loops (on screen, with rows going down), negative for anticlockwise.
+    pub fn signed_area(&self) -> i64 {
+        let twice: i64 = self
+            .edges()
+            .map(|(a, b)| a.col as i64 * b.row as i64 - b.col as i64 * a.row as i64)
+            .sum();
+        twice / 2
+    }
 
-            seen.extend(points.iter());
-            regions.push(Region { points });
-        }
+    pub fn is_hole(&self) -> bool {
+        self.signed_area() < 0
+    }
 
-        regions
+    fn edges(&self) -> impl Iterator<Item = (Position, Position)> + '_ {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
     }
+}
 
-    fn area(&self) -> u64 {
-        self.points.len() as u64
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub plant: char,
+    pub area: u64,
+    /// Inclusive top-left and bottom-right cells.
+    pub bounding_box: (Position, Position),
+    /// Outer boundaries and holes together, see `Polygon::is_hole`.
+    pub boundaries: Vec<Polygon>,
+    /// Indices of the regions this one sits in a hole of, outermost first.
+    pub enclosed_by: Vec<usize>,
+}
+
+impl Region {
+    #[allow(dead_code)]
+    pub fn outer(&self) -> impl Iterator<Item = &Polygon> {
+        self.boundaries.iter().filter(|p| !p.is_hole())
+    }
+
+    pub fn holes(&self) -> impl Iterator<Item = &Polygon> {
+        self.boundaries.iter().filter(|p| p.is_hole())
     }
 
-    fn perimiter(&self) -> u64 {
-        self.points
+    pub fn perimeter(&self) -> u64 {
+        self.boundaries.iter().map(|p| p.perimeter()).sum()
+    }
+
+    pub fn sides(&self) -> u64 {
+        self.boundaries.iter().map(|p| p.sides()).sum()
+    }
+}
+
+/// Totals for one type of plant, over all its regions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlantPricing {
+    pub regions: usize,
+    pub area: u64,
+    pub perimeter: u64,
+    pub sides: u64,
+    /// Area times perimeter, per region.
+    pub price: u64,
+    /// Area times sides, per region.
+    pub bulk_price: u64,
+}
+
+pub struct Garden {
+    /// Region index of every cell.
+    pub labels: Grid<usize>,
+    pub regions: Vec<Region>,
+}
+
+impl Garden {
+    pub fn new(grid: &Grid<char>) -> Self {
+        let components = components::label_equal(grid, Connectivity::Four);
+
+        let mut regions = components
+            .components
             .iter()
-            .map(|p| {
-                Direction::ALL
-                    .into_iter()
-                    .map(|d| {
-                        if !self.points.contains(&p.moved_in(d)) {
-                            1
-                        } else {
-                            0
-                        }
-                    })
-                    .sum::<u64>()
+            .map(|c| Region {
+                plant: *grid.get(c.cells[0]).unwrap(),
+                area: c.area(),
+                bounding_box: c.bounding_box,
+                boundaries: trace_boundaries(&c.cells),
+                enclosed_by: Vec::new(),
             })
-            .sum()
-    }
+            .collect::<Vec<_>>();
+
+        let mut garden = Garden {
+            labels: components.labels,
+            regions: Vec::new(),
+        };
 
-    // This is awful but already late and it works so :)
-    fn corners(&self) -> u64 {
-        self.points
+        for i in 0..regions.len() {
+            if regions[i].holes().next().is_none() {
+                continue;
+            }
+            for j in garden.inside_holes(i, regions[i].bounding_box) {
+                regions[j].enclosed_by.push(i);
+            }
+        }
+        // Anything enclosing a region's encloser encloses the region too,
+        // so the more enclosers an encloser has the further in it is.
+        let depths = regions
             .iter()
-            .map(|p| {
-                let is_outside = |d: Direction| -> bool {
-                    let p = p.moved_in(d);
-                    if !self.points.contains(&p) {
-                        true
-                    } else {
-                        false
-                    }
-                };
-                let neighbour_is_outside: [bool; 4] = [
-                    is_outside(Direction::Up),
-                    is_outside(Direction::Left),
-                    is_outside(Direction::Down),
-                    is_outside(Direction::Right),
-                ];
-
-                let outside_count = neighbour_is_outside.iter().cloned().filter(|b| *b).count();
-
-                let outer_corners = if outside_count == 0 || outside_count == 1 {
-                    0
-                } else if outside_count == 2 {
-                    if (neighbour_is_outside[0] && neighbour_is_outside[2])
-                        || (neighbour_is_outside[1] && neighbour_is_outside[3])
-                    {
-                        0
-                    } else {
-                        1
-                    }
-                } else if outside_count == 3 {
-                    2
-                } else {
-                    4
-                };
-
-                let mut inner_corners = 0;
-                if !neighbour_is_outside[0]
-                    && !neighbour_is_outside[1]
-                    && !self
-                        .points
-                        .contains(&p.moved_in(Direction::Up).moved_in(Direction::Left))
-                {
-                    inner_corners += 1;
-                }
-                if !neighbour_is_outside[1]
-                    && !neighbour_is_outside[2]
-                    && !self
-                        .points
-                        .contains(&p.moved_in(Direction::Left).moved_in(Direction::Down))
-                {
-                    inner_corners += 1;
-                }
-                if !neighbour_is_outside[2]
-                    && !neighbour_is_outside[3]
-                    && !self
-                        .points
-                        .contains(&p.moved_in(Direction::Down).moved_in(Direction::Right))
-                {
-                    inner_corners += 1;
+            .map(|r| r.enclosed_by.len())
+            .collect::<Vec<_>>();
+        for r in &mut regions {
+            r.enclosed_by.sort_by_key(|i| depths[*i]);
+        }
+
+        garden.regions = regions;
+        garden
+    }
+
+    /// Regions in the holes of region `i`, found by filling in everything else in its bounding box.
+    /// Whatever reaches the edge of the box is outside the region.
+    fn inside_holes(&self, i: usize, (min, max): (Position, Position)) -> Vec<usize> {
+        let in_box = |p: Position| {
+            (min.row..=max.row).contains(&p.row) && (min.col..=max.col).contains(&p.col)
+        };
+        let on_edge = |p: Position| {
+            p.row == min.row || p.row == max.row || p.col == min.col || p.col == max.col
+        };
+
+        let mut outside = HashSet::new();
+        let mut stack = Vec::new();
+        for row in min.row..=max.row {
+            for col in min.col..=max.col {
+                let p = Position::new(row, col);
+                if on_edge(p) && self.labels.get(p) != Some(&i) && outside.insert(p) {
+                    stack.push(p);
                 }
-                if !neighbour_is_outside[3]
-                    && !neighbour_is_outside[0]
-                    && !self
-                        .points
-                        .contains(&p.moved_in(Direction::Right).moved_in(Direction::Up))
-                {
-                    inner_corners += 1;
+            }
+        }
+        while let Some(p) = stack.pop() {
+            for n in p.neighbours() {
+                if in_box(n) && self.labels.get(n) != Some(&i) && outside.insert(n) {
+                    stack.push(n);
                 }
+            }
+        }
 
-                outer_corners + inner_corners
-            })
-            .sum()
+        let mut inside = Vec::new();
+        for row in min.row..=max.row {
+            for col in min.col..=max.col {
+                let p = Position::new(row, col);
+                let label = *self.labels.get(p).unwrap();
+                if label != i && !outside.contains(&p) && !inside.contains(&label) {
+                    inside.push(label);
+                }
+            }
+        }
+        inside
     }
-}
 
-fn flood_fill(grid: &Grid<char>, from: Position) -> HashSet<Position> {
-    let target = grid.get(from).unwrap().clone();
+    /// Regions directly in a hole of region `i`, not inside some other region in that hole.
+    #[allow(dead_code)]
+    pub fn directly_inside(&self, i: usize) -> Vec<usize> {
+        (0..self.regions.len())
+            .filter(|j| self.regions[*j].enclosed_by.last() == Some(&i))
+            .collect()
+    }
 
-    let mut points = HashSet::new();
-    points.insert(from);
-    let mut q = VecDeque::new();
-    q.push_back(from);
+    pub fn pricing(&self) -> BTreeMap<char, PlantPricing> {
+        let mut report: BTreeMap<char, PlantPricing> = BTreeMap::new();
+        for r in &self.regions {
+            let (perimeter, sides) = (r.perimeter(), r.sides());
+            let p = report.entry(r.plant).or_default();
+            p.regions += 1;
+            p.area += r.area;
+            p.perimeter += perimeter;
+            p.sides += sides;
+            p.price += r.area * perimeter;
+            p.bulk_price += r.area * sides;
+        }
+        report
+    }
+}
 
-    while let Some(p) = q.pop_front() {
-        for d in Direction::ALL {
-            let p = p.moved_in(d);
+/// Follows the fence around `cells` into closed loops.
+fn trace_boundaries(cells: &[Position]) -> Vec<Polygon> {
+    let cell_set = cells.iter().copied().collect::<HashSet<_>>();
 
-            if points.contains(&p) {
-                continue;
+    // Unit fence edges, keyed by the lattice point they start at, heading so the cell is on the right.
+    let mut outgoing: HashMap<Position, Vec<Direction>> = HashMap::new();
+    for &cell in cells {
+        let edges = [
+            (Direction::Up, cell, Direction::Right),
+            (
+                Direction::Right,
+                cell + Position::new(0, 1),
+                Direction::Down,
+            ),
+            (Direction::Down, cell + Position::new(1, 1), Direction::Left),
+            (Direction::Left, cell + Position::new(1, 0), Direction::Up),
+        ];
+        for (side, start, heading) in edges {
+            if !cell_set.contains(&cell.moved_in(side)) {
+                outgoing.entry(start).or_default().push(heading);
             }
+        }
+    }
 
-            let Some(c) = grid.get(p) else {
-                continue;
-            };
+    let mut starts = outgoing.keys().copied().collect::<Vec<_>>();
+    starts.sort_by_key(|p| (p.row, p.col));
 
-            if *c != target {
-                continue;
+    let mut polygons = Vec::new();
+    for start in starts {
+        while let Some(first) = outgoing.get_mut(&start).and_then(|hs| hs.pop()) {
+            // Heading out of each lattice point on the loop.
+            let mut edges = vec![(start, first)];
+            let (mut p, mut heading) = (start.moved_in(first), first);
+            loop {
+                let options = outgoing.get_mut(&p).unwrap();
+                // Where the region touches itself diagonally there are two ways on.
+                // Turning left keeps to the cell outside the region, so each loop goes
+                // around a single orthogonally connected patch of outside, same as `inside_holes`.
+                let next = [
+                    heading.rotated_anticlockwise(),
+                    heading,
+                    heading.rotated_clockwise(),
+                ]
+                .into_iter()
+                .find(|h| options.contains(h) || (p == start && *h == first))
+                .unwrap();
+                if p == start && next == first {
+                    break;
+                }
+                options.retain(|h| *h != next);
+
+                edges.push((p, next));
+                (p, heading) = (p.moved_in(next), next);
             }
 
-            points.insert(p);
-            q.push_back(p);
+            // Only the points where the heading changes.
+            let vertices = edges
+                .iter()
+                .zip(edges.iter().cycle().skip(edges.len() - 1))
+                .filter(|((_, heading), (_, previous))| heading != previous)
+                .map(|((p, _), _)| *p)
+                .collect();
+            polygons.push(Polygon { vertices });
         }
     }
 
-    points
+    polygons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "RRRRIICCFF
+RRRRIICCCF
+VVRRRCCFFF
+VVRCCCJFFF
+VVVVCJJCFE
+VVIVCCJJEE
+VVIIICJJEE
+MIIIIIJJEE
+MIIISIJEEE
+MMMISSJEEE";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 1930);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 1206);
+    }
+
+    #[test]
+    fn test_holes() {
+        let garden = Garden::new(&parse("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO"));
+        let o = &garden.regions[0];
+
+        assert_eq!(o.plant, 'O');
+        assert_eq!(o.area, 21);
+        assert_eq!(o.outer().count(), 1);
+        assert_eq!(o.holes().count(), 4);
+        assert_eq!(o.perimeter(), 36);
+        assert_eq!(o.sides(), 20);
+        assert_eq!(o.outer().next().unwrap().signed_area(), 25);
+        assert_eq!(o.holes().next().unwrap().signed_area(), -1);
+        assert_eq!(o.bounding_box, (Position::new(0, 0), Position::new(4, 4)));
+
+        assert_eq!(garden.directly_inside(0).len(), 4);
+        assert!(garden.regions[1..].iter().all(|r| r.enclosed_by == vec![0]));
+    }
+
+    #[test]
+    fn test_nested_and_diagonal() {
+        // The B region touches itself diagonally inside A, and C sits in B's hole.
+        let garden = Garden::new(&parse(
+            "AAAAAA\nABBBBA\nABCCBA\nABBBBA\nAAAAAA\nAAABBA\nAABAAA\nAAAAAA",
+        ));
+        let by_plant = |c: char| {
+            (0..garden.regions.len())
+                .filter(|i| garden.regions[*i].plant == c)
+                .collect::<Vec<_>>()
+        };
+        let (a, b, c) = (by_plant('A')[0], by_plant('B')[0], by_plant('C')[0]);
+
+        assert_eq!(garden.regions[c].enclosed_by, vec![a, b]);
+        assert_eq!(garden.directly_inside(b), vec![c]);
+        assert_eq!(garden.directly_inside(a).len(), 3);
+        assert_eq!(garden.regions[c].sides(), 4);
+
+        // The AoC example where two B regions meet at a corner inside A:
+        // they're separate holes of 4 sides each.
+        let input = parse("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA");
+        let garden = Garden::new(&input);
+        assert_eq!(part2(&input), 368);
+        assert_eq!(garden.regions[0].sides(), 12);
+        assert_eq!(garden.regions[0].holes().count(), 2);
+        assert!(garden.regions[0].holes().all(|h| h.sides() == 4));
+    }
+
+    #[test]
+    fn test_pinched_ring() {
+        // The ring only closes diagonally at the bottom right, but the middle cell
+        // still has no way out orthogonally so it's a hole.
+        let garden = Garden::new(&parse("XXX\nX.X\nXX."));
+        let x = &garden.regions[0];
+
+        assert_eq!(x.holes().count(), 1);
+        assert_eq!(x.outer().next().unwrap().sides(), 6);
+        assert_eq!(x.sides(), 10);
+        assert_eq!(x.perimeter(), 16);
+
+        let hole = garden.labels.get(Position::new(1, 1)).unwrap();
+        let outside = garden.labels.get(Position::new(2, 2)).unwrap();
+        assert_eq!(garden.regions[*hole].enclosed_by, vec![0]);
+        assert!(garden.regions[*outside].enclosed_by.is_empty());
+    }
+
+    #[test]
+    fn test_pricing() {
+        let pricing = Garden::new(&parse(EXAMPLE)).pricing();
+
+        assert_eq!(pricing.values().map(|p| p.price).sum::<u64>(), 1930);
+        assert_eq!(pricing.values().map(|p| p.bulk_price).sum::<u64>(), 1206);
+        assert_eq!(
+            pricing[&'C'],
+            PlantPricing {
+                regions: 2,
+                area: 15,
+                perimeter: 32,
+                sides: 26,
+                price: 14 * 28 + 4,
+                bulk_price: 14 * 22 + 4
+            }
+        );
+    }
 }
@@ -1,78 +1,396 @@
-use regex::Regex;
+use crate::util::{
+    math::extended_gcd,
+    parse::{sections, Pattern},
+    position::Position,
+};
 
+// X is the column, Y the row.
 pub struct Machine {
-    a_x: i64,
-    a_y: i64,
-
-    b_x: i64,
-    b_y: i64,
-
-    p_x: i64,
-    p_y: i64,
+    a: Position<i128>,
+    b: Position<i128>,
+    prize: Position<i128>,
 }
 
 type Input = Vec<Machine>;
 
+const BUTTON_A: Pattern = Pattern::new("Button A: X+{}, Y+{}");
+const BUTTON_B: Pattern = Pattern::new("Button B: X+{}, Y+{}");
+const PRIZE: Pattern = Pattern::new("Prize: X={}, Y={}");
+
 pub fn parse(input: &str) -> Input {
-    let mut machines = Vec::new();
-
-    let a = Regex::new("Button A: X\\+(\\d+), Y\\+(\\d+)").unwrap();
-    let b = Regex::new("Button B: X\\+(\\d+), Y\\+(\\d+)").unwrap();
-    let prize = Regex::new("Prize: X=(\\d+), Y=(\\d+)").unwrap();
-
-    let a_matches = a.captures_iter(input);
-    let b_matches = b.captures_iter(input);
-    let prize_matches = prize.captures_iter(input);
-
-    for ((a, b), p) in a_matches.zip(b_matches).zip(prize_matches) {
-        let a_x = a.get(1).unwrap().as_str().parse::<u64>().unwrap() as i64;
-        let a_y = a.get(2).unwrap().as_str().parse::<u64>().unwrap() as i64;
-
-        let b_x = b.get(1).unwrap().as_str().parse::<u64>().unwrap() as i64;
-        let b_y = b.get(2).unwrap().as_str().parse::<u64>().unwrap() as i64;
-
-        let p_x = p.get(1).unwrap().as_str().parse::<u64>().unwrap() as i64;
-        let p_y = p.get(2).unwrap().as_str().parse::<u64>().unwrap() as i64;
-
-        machines.push(Machine {
-            a_x,
-            a_y,
-            b_x,
-            b_y,
-            p_x,
-            p_y,
-        });
-    }
+    sections(input)
+        .map(|section| {
+            let mut lines = section.lines();
+            let mut next = |pattern: Pattern| {
+                let (x, y): (i128, i128) = pattern.parse(lines.next().unwrap()).unwrap();
+                Position::new(y, x)
+            };
 
-    machines
+            Machine {
+                a: next(BUTTON_A),
+                b: next(BUTTON_B),
+                prize: next(PRIZE),
+            }
+        })
+        .collect()
 }
 
 pub fn part1(input: &Input) -> u64 {
-    input
-        .iter()
-        .map(|m| {
-            let inv_det = (m.a_x * m.b_y) - (m.b_x * m.a_y);
-            let a = ((m.b_y * m.p_x) - (m.b_x * m.p_y)) / inv_det;
-            let b = ((m.a_x * m.p_y) - (m.a_y * m.p_x)) / inv_det;
-
-            if a * m.a_x + b * m.b_x == m.p_x && a * m.a_y + b * m.b_y == m.p_y {
-                3 * a as u64 + b as u64
-            } else {
-                0
-            }
-        })
-        .sum()
+    total_cost(input, &Rules::PART1)
 }
 
 pub fn part2(input: &Input) -> u64 {
     let input = input
         .iter()
         .map(|m| Machine {
-            p_x: m.p_x + 10000000000000,
-            p_y: m.p_y + 10000000000000,
+            prize: m.prize + Position::new(10000000000000, 10000000000000),
             ..*m
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    total_cost(&input, &Rules::PART2)
+}
+
+fn total_cost(machines: &[Machine], rules: &Rules) -> u64 {
+    machines
+        .iter()
+        .filter_map(|m| m.solve(rules).cheapest())
+        .map(|p| rules.cost(p) as u64)
+        .sum()
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Rules {
+    /// Tokens per press, which can't be negative.
+    pub cost_a: i128,
+    pub cost_b: i128,
+    /// Most times each button can be pressed.
+    pub max_presses: Option<i128>,
+}
+
+impl Rules {
+    pub const PART1: Rules = Rules {
+        cost_a: 3,
+        cost_b: 1,
+        max_presses: Some(100),
+    };
+    pub const PART2: Rules = Rules {
+        max_presses: None,
+        ..Rules::PART1
+    };
 
-    part1(&input)
+    pub fn cost(&self, presses: Presses) -> i128 {
+        self.cost_a * presses.a + self.cost_b * presses.b
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Presses {
+    pub a: i128,
+    pub b: i128,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    NoSolution,
+    Unique(Presses),
+    /// Only possible when the buttons move in the same direction.
+    /// `ways` is `None` when there's no end to them.
+    Multiple {
+        cheapest: Presses,
+        ways: Option<u128>,
+    },
+}
+
+impl Outcome {
+    pub fn cheapest(&self) -> Option<Presses> {
+        match self {
+            Outcome::NoSolution => None,
+            Outcome::Unique(p) | Outcome::Multiple { cheapest: p, .. } => Some(*p),
+        }
+    }
+}
+
+impl Machine {
+    pub fn solve(&self, rules: &Rules) -> Outcome {
+        assert!(
+            rules.cost_a >= 0 && rules.cost_b >= 0,
+            "costs can't be negative"
+        );
+        let (a, b, prize) = (self.a, self.b, self.prize);
+        let within = |n: i128| n >= 0 && rules.max_presses.is_none_or(|max| n <= max);
+
+        // Cramer's rule, when the buttons aren't collinear there's only one way to reach any point.
+        let det = a.col * b.row - b.col * a.row;
+        if det != 0 {
+            let n_a = b.row * prize.col - b.col * prize.row;
+            let n_b = a.col * prize.row - a.row * prize.col;
+            if n_a % det != 0 || n_b % det != 0 {
+                return Outcome::NoSolution;
+            }
+
+            let presses = Presses {
+                a: n_a / det,
+                b: n_b / det,
+            };
+            return if within(presses.a) && within(presses.b) {
+                Outcome::Unique(presses)
+            } else {
+                Outcome::NoSolution
+            };
+        }
+
+        // Otherwise the prize has to be on the same line, then it's one equation along it.
+        let cross = |p: Position<i128>| p.col * prize.row - p.row * prize.col;
+        if cross(a) != 0 || cross(b) != 0 {
+            return Outcome::NoSolution;
+        }
+        let outcome = if (a.col, b.col) != (0, 0) {
+            solve_line(a.col, b.col, prize.col, rules)
+        } else {
+            solve_line(a.row, b.row, prize.row, rules)
+        };
+
+        match outcome.cheapest() {
+            Some(p) if a * p.a + b * p.b != prize => Outcome::NoSolution,
+            _ => outcome,
+        }
+    }
+}
+
+/// Cheapest `a * u + b * v == w`, for presses `a` and `b`.
+fn solve_line(u: i128, v: i128, w: i128, rules: &Rules) -> Outcome {
+    let max = rules.max_presses;
+
+    if (u, v) == (0, 0) {
+        return if w == 0 {
+            Outcome::Multiple {
+                cheapest: Presses { a: 0, b: 0 },
+                ways: max.map(|m| ((m + 1) * (m + 1)) as u128),
+            }
+        } else {
+            Outcome::NoSolution
+        };
+    }
+
+    // Every solution is `a = a0 + k*s, b = b0 - k*t` for integer `k`.
+    let (a0, s, b0, t) = match (u, v) {
+        // Only one button does anything, the other is free.
+        (0, v) if w % v == 0 => (0, 1, w / v, 0),
+        (u, 0) if w % u == 0 => (w / u, 0, 0, -1),
+        (0, _) | (_, 0) => return Outcome::NoSolution,
+        (u, v) => {
+            let (g, x, y) = extended_gcd(u, v);
+            if w % g != 0 {
+                return Outcome::NoSolution;
+            }
+            (x * (w / g), v / g, y * (w / g), u / g)
+        }
+    };
+
+    // 0 <= a <= max and 0 <= b <= max, as bounds on k.
+    let ranges = [
+        k_range(s, Some(-a0), max.map(|m| m - a0)),
+        k_range(-t, Some(-b0), max.map(|m| m - b0)),
+    ];
+    let Some(ranges) = ranges.into_iter().collect::<Option<Vec<_>>>() else {
+        return Outcome::NoSolution;
+    };
+    let lo = ranges.iter().filter_map(|(lo, _)| *lo).max();
+    let hi = ranges.iter().filter_map(|(_, hi)| *hi).min();
+    if let (Some(lo), Some(hi)) = (lo, hi) {
+        if lo > hi {
+            return Outcome::NoSolution;
+        }
+    }
+
+    // Cost is linear in k, so the cheapest is at one end.
+    let slope = rules.cost_a * s - rules.cost_b * t;
+    let k = if slope >= 0 { lo.or(hi) } else { hi.or(lo) };
+    // Presses can't go negative, and one of them always changes with k.
+    let k = k.expect("k is bounded on at least one side");
+    let cheapest = Presses {
+        a: a0 + k * s,
+        b: b0 - k * t,
+    };
+
+    match (lo, hi) {
+        (Some(lo), Some(hi)) if lo == hi => Outcome::Unique(cheapest),
+        (Some(lo), Some(hi)) => Outcome::Multiple {
+            cheapest,
+            ways: Some((hi - lo + 1) as u128),
+        },
+        _ => Outcome::Multiple {
+            cheapest,
+            ways: None,
+        },
+    }
+}
+
+/// The `k` with `lo <= k * c <= hi`, as optional bounds either side,
+/// or `None` if there aren't any.
+fn k_range(c: i128, lo: Option<i128>, hi: Option<i128>) -> Option<(Option<i128>, Option<i128>)> {
+    if c == 0 {
+        let ok = lo.is_none_or(|lo| lo <= 0) && hi.is_none_or(|hi| hi >= 0);
+        return ok.then_some((None, None));
+    }
+
+    let ceil_div = |n: i128| -floor_div(-n, c);
+    Some(if c > 0 {
+        (lo.map(ceil_div), hi.map(|n| floor_div(n, c)))
+    } else {
+        (hi.map(ceil_div), lo.map(|n| floor_div(n, c)))
+    })
+}
+
+fn floor_div(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    if n % d != 0 && (n < 0) != (d < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 480);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 875318608908);
+    }
+
+    fn machine(a: (i128, i128), b: (i128, i128), prize: (i128, i128)) -> Machine {
+        // Given as (X, Y) like the input.
+        let pos = |(x, y)| Position::new(y, x);
+        Machine {
+            a: pos(a),
+            b: pos(b),
+            prize: pos(prize),
+        }
+    }
+
+    #[test]
+    fn test_unique() {
+        let machines = parse(EXAMPLE);
+        assert_eq!(
+            machines[0].solve(&Rules::PART1),
+            Outcome::Unique(Presses { a: 80, b: 40 })
+        );
+        assert_eq!(machines[1].solve(&Rules::PART1), Outcome::NoSolution);
+
+        // Fine without the press limit.
+        let far = machine((1, 0), (0, 1), (150, 1));
+        assert_eq!(far.solve(&Rules::PART1), Outcome::NoSolution);
+        assert_eq!(
+            far.solve(&Rules::PART2),
+            Outcome::Unique(Presses { a: 150, b: 1 })
+        );
+    }
+
+    #[test]
+    fn test_collinear() {
+        let m = machine((1, 1), (2, 2), (10, 10));
+        assert_eq!(
+            m.solve(&Rules::PART2),
+            Outcome::Multiple {
+                cheapest: Presses { a: 0, b: 5 },
+                ways: Some(6)
+            }
+        );
+
+        let limited = Rules {
+            max_presses: Some(4),
+            ..Rules::PART1
+        };
+        assert_eq!(
+            m.solve(&limited),
+            Outcome::Multiple {
+                cheapest: Presses { a: 2, b: 4 },
+                ways: Some(2)
+            }
+        );
+
+        let expensive_b = Rules {
+            cost_b: 10,
+            ..Rules::PART2
+        };
+        assert_eq!(
+            m.solve(&expensive_b).cheapest(),
+            Some(Presses { a: 10, b: 0 })
+        );
+
+        let only_one = Rules {
+            max_presses: Some(3),
+            ..Rules::PART1
+        };
+        assert_eq!(
+            machine((1, 1), (3, 3), (7, 7)).solve(&only_one),
+            Outcome::Unique(Presses { a: 1, b: 2 })
+        );
+
+        assert_eq!(
+            machine((2, 2), (4, 4), (5, 5)).solve(&Rules::PART2),
+            Outcome::NoSolution
+        );
+        assert_eq!(
+            machine((1, 1), (2, 2), (10, 11)).solve(&Rules::PART2),
+            Outcome::NoSolution
+        );
+    }
+
+    #[test]
+    fn test_degenerate() {
+        // Buttons going opposite ways can be pressed forever.
+        assert_eq!(
+            machine((3, 0), (-2, 0), (1, 0)).solve(&Rules::PART2),
+            Outcome::Multiple {
+                cheapest: Presses { a: 1, b: 1 },
+                ways: None
+            }
+        );
+
+        // B does nothing.
+        assert_eq!(
+            machine((0, 2), (0, 0), (0, 8)).solve(&Rules::PART1),
+            Outcome::Multiple {
+                cheapest: Presses { a: 4, b: 0 },
+                ways: Some(101)
+            }
+        );
+
+        assert_eq!(
+            machine((0, 0), (0, 0), (0, 0)).solve(&Rules::PART1),
+            Outcome::Multiple {
+                cheapest: Presses { a: 0, b: 0 },
+                ways: Some(101 * 101)
+            }
+        );
+        assert_eq!(
+            machine((0, 0), (0, 0), (1, 0)).solve(&Rules::PART1),
+            Outcome::NoSolution
+        );
+    }
 }
@@ -1,79 +1,238 @@
-use regex::Regex;
+use crate::util::{grid::Grid, math::crt, parse::Pattern, position::Position};
 
 type Input = Vec<Robot>;
 
+const ROBOT: Pattern = Pattern::new("p={},{} v={},{}");
+
 pub fn parse(input: &str) -> Input {
-    Regex::new("p=(\\d+),(\\d+) v=(-?\\d+),(-?\\d+)")
-        .unwrap()
-        .captures_iter(input)
-        .map(|c| Robot {
-            p: (
-                c.get(1).unwrap().as_str().parse::<i32>().unwrap(),
-                c.get(2).unwrap().as_str().parse::<i32>().unwrap(),
-            ),
-            v: (
-                c.get(3).unwrap().as_str().parse::<i32>().unwrap(),
-                c.get(4).unwrap().as_str().parse::<i32>().unwrap(),
-            ),
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            let (p_x, p_y, v_x, v_y) = ROBOT.parse(l).map_err(|e| e.at_line(i + 1)).unwrap();
+            Robot {
+                p: Position::new(p_y, p_x),
+                v: Position::new(v_y, v_x),
+            }
         })
         .collect()
 }
 
 pub fn part1(input: &Input) -> u32 {
-    input
-        .iter()
-        .map(|r| r.position_after(100))
-        .fold([0u32; 4], |mut acc, (x, y)| {
-            if x < COLS / 2 && y < ROWS / 2 {
-                acc[0] += 1;
-            } else if x < COLS / 2 && y > ROWS / 2 {
-                acc[1] += 1;
-            } else if x > COLS / 2 && y < ROWS / 2 {
-                acc[2] += 1;
-            } else if x > COLS / 2 && y > ROWS / 2 {
-                acc[3] += 1;
-            }
-            acc
-        })
-        .iter()
-        .product()
+    Arena::PUZZLE.safety_factor(input, 100)
 }
 
 pub fn part2(input: &Input) -> u64 {
-    // Guess the image will be in the center of grid,
-    // so avg distance to center will be lowest.
-    // May repeat many times but this should give the first occurence.
-    (0..COLS * ROWS)
-        .into_iter()
-        .map(|s| {
-            input
-                .iter()
-                .map(|r| {
-                    let (x, y) = r.position_after(s);
-                    (COLS / 2).abs_diff(x) + (ROWS / 2).abs_diff(y)
-                })
-                .sum::<u32>()
-        })
-        .enumerate()
-        .min_by(|(_, a), (_, b)| a.cmp(b))
-        .unwrap()
-        .0 as u64
+    Arena::PUZZLE.easter_egg(input).unwrap()
 }
 
-const ROWS: i32 = 103;
-const COLS: i32 = 101;
-
+// X is the column, Y the row.
 pub struct Robot {
-    p: (i32, i32),
-    v: (i32, i32),
+    p: Position,
+    v: Position,
+}
+
+/// The space the robots wrap around in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arena {
+    pub rows: i32,
+    pub cols: i32,
 }
 
-impl Robot {
-    fn position_after(&self, s: i32) -> (i32, i32) {
-        (
-            // Rust % is remainder not modulo so add multiple of rows/cols to prevent negatives.
-            (self.p.0 + (s * self.v.0) + (s * COLS)) % COLS,
-            (self.p.1 + (s * self.v.1) + (s * ROWS)) % ROWS,
+impl Arena {
+    pub const PUZZLE: Arena = Arena {
+        rows: 103,
+        cols: 101,
+    };
+    #[cfg(test)]
+    pub const EXAMPLE: Arena = Arena { rows: 7, cols: 11 };
+
+    pub fn position_after(&self, robot: &Robot, s: u64) -> Position {
+        // Each axis repeats on its own, reducing first keeps the multiplication small.
+        let (s_row, s_col) = ((s % self.rows as u64) as i32, (s % self.cols as u64) as i32);
+        let moved = Position::new(robot.v.row * s_row, robot.v.col * s_col);
+        (robot.p + moved) % Position::new(self.rows, self.cols)
+    }
+
+    /// Which quadrant, in reading order, `pos` is in.
+    /// The middle row and column don't count when there's an odd number of them.
+    pub fn quadrant(&self, pos: Position) -> Option<usize> {
+        let half =
+            |n: i32, x: i32| (n % 2 == 0 || x != n / 2).then_some((x >= (n + 1) / 2) as usize);
+        Some(half(self.rows, pos.row)? * 2 + half(self.cols, pos.col)?)
+    }
+
+    pub fn quadrant_counts(&self, positions: impl IntoIterator<Item = Position>) -> [u32; 4] {
+        let mut counts = [0; 4];
+        for q in positions.into_iter().filter_map(|p| self.quadrant(p)) {
+            counts[q] += 1;
+        }
+        counts
+    }
+
+    /// Product of the quadrant counts after `s` seconds.
+    pub fn safety_factor(&self, robots: &[Robot], s: u64) -> u32 {
+        self.quadrant_counts(robots.iter().map(|r| self.position_after(r, s)))
+            .iter()
+            .product()
+    }
+
+    /// First second the robots bunch up into a picture.
+    ///
+    /// Columns repeat every `cols` seconds and rows every `rows` seconds, so find the
+    /// second in each cycle where that axis is least spread out, then combine the two with CRT.
+    /// `None` if the two cycles can't line up.
+    pub fn easter_egg(&self, robots: &[Robot]) -> Option<u64> {
+        // Proportional to the variance, without leaving integers.
+        let spread = |values: &mut dyn Iterator<Item = i32>| {
+            let (n, sum, sum_sq) = values.fold((0i64, 0i64, 0i64), |(n, s, sq), x| {
+                (n + 1, s + x as i64, sq + (x as i64) * (x as i64))
+            });
+            n * sum_sq - sum * sum
+        };
+
+        let best_col = (0..self.cols as u64)
+            .min_by_key(|s| spread(&mut robots.iter().map(|r| self.position_after(r, *s).col)))?;
+        let best_row = (0..self.rows as u64)
+            .min_by_key(|s| spread(&mut robots.iter().map(|r| self.position_after(r, *s).row)))?;
+
+        let (s, _) = crt(&[
+            (best_col as i128, self.cols as i128),
+            (best_row as i128, self.rows as i128),
+        ])
+        .ok()?;
+        Some(s as u64)
+    }
+
+    /// Robot count per tile after `s` seconds.
+    pub fn frame(&self, robots: &[Robot], s: u64) -> Grid<u32> {
+        let mut frame = Grid::new(self.rows as usize, self.cols as usize, 0);
+        for r in robots {
+            let p = self.position_after(r, s);
+            frame.set(p, frame.get(p).unwrap() + 1);
+        }
+        frame
+    }
+
+    /// The puzzle's notation, robot counts with `.` for none and `+` past 9.
+    pub fn frame_text(&self, robots: &[Robot], s: u64) -> String {
+        self.frame(robots, s).render(|_, n| match *n {
+            0 => '.',
+            n @ 1..=9 => char::from_digit(n, 10).unwrap(),
+            _ => '+',
+        })
+    }
+
+    /// Plain (ASCII) PGM image with the robot count as the grey level, for image viewers.
+    pub fn frame_pgm(&self, robots: &[Robot], s: u64) -> String {
+        let frame = self.frame(robots, s);
+        let max = frame.iter().map(|(_, n)| n).max().unwrap_or(0).max(1);
+
+        format!(
+            "P2\n{} {}\n{}\n{}",
+            self.cols,
+            self.rows,
+            max,
+            frame.render_separated(" ", |_, n| *n)
         )
     }
 }
+
+/// Prints the frame with the easter egg in it, for `day14-frame`.
+/// As a PGM image it can go straight to a file, e.g. `day14-frame pgm > tree.pgm`.
+pub fn print_easter_egg(robots: &Input, pgm: bool) {
+    let arena = Arena::PUZZLE;
+    let Some(s) = arena.easter_egg(robots) else {
+        println!("no frame stands out");
+        return;
+    };
+
+    if pgm {
+        print!("{}", arena.frame_pgm(robots, s));
+    } else {
+        println!("after {} seconds:\n{}", s, arena.frame_text(robots, s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::Rng;
+
+    const EXAMPLE: &str = "p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(Arena::EXAMPLE.safety_factor(&parse(EXAMPLE), 100), 12);
+    }
+
+    #[test]
+    fn test_frames() {
+        let robots = parse(EXAMPLE);
+        let expected = "......2..1.
+...........
+1..........
+.11........
+.....1.....
+...12......
+.1....1....
+";
+        assert_eq!(Arena::EXAMPLE.frame_text(&robots, 100), expected);
+        assert_eq!(
+            Arena::EXAMPLE
+                .quadrant_counts(robots.iter().map(|r| Arena::EXAMPLE.position_after(r, 100))),
+            [1, 3, 4, 1]
+        );
+
+        let pgm = Arena::EXAMPLE.frame_pgm(&robots, 100);
+        assert!(pgm.starts_with("P2\n11 7\n2\n0 0 0 0 0 0 2 0 0 1 0\n"));
+        assert_eq!(pgm.lines().count(), 3 + 7);
+    }
+
+    #[test]
+    fn test_easter_egg() {
+        // Deterministic pseudo-random robots, most of which end up in a small square at `target`.
+        let mut rng = Rng::new(11);
+        let mut next = |n: u64| rng.below(n) as i32;
+
+        let arena = Arena::PUZZLE;
+        let target = 6789;
+        let robots = (0..500)
+            .map(|i| {
+                let v = Position::new(next(201) - 100, next(201) - 100);
+                let p = Position::new(next(arena.rows as u64), next(arena.cols as u64));
+                if i % 3 == 0 {
+                    return Robot { p, v };
+                }
+
+                // Wind back from somewhere in the square.
+                let end = Position::new(40 + next(15), 50 + next(15));
+                let back = Robot { p: end, v: -v };
+                Robot {
+                    p: arena.position_after(&back, target),
+                    v,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(arena.easter_egg(&robots), Some(target));
+        let frame = arena.frame(&robots, target);
+        let in_square = frame
+            .iter()
+            .filter(|(p, _)| (40..55).contains(&p.row) && (50..65).contains(&p.col))
+            .map(|(_, n)| n)
+            .sum::<u32>();
+        assert!(in_square >= 333);
+    }
+}
@@ -3,269 +3,685 @@ use crate::util::{
     position::{Direction, Position},
 };
 
-type Input = (Grid<P1Cell>, Grid<P2Cell>, Vec<Direction>);
+type Input = (Grid<Tile>, Vec<Direction>);
 
 pub fn parse(input: &str) -> Input {
     let map = input
         .lines()
-        .take_while(|l| *l != "")
-        .fold(String::with_capacity(input.len()), |s, l| {
-            format!("{}{}\n", s, l)
-        })
-        .as_str()
-        .into();
-    let map2 = input
-        .lines()
-        .take_while(|l| *l != "")
-        .map(|l| {
-            l.chars()
-                .fold(String::with_capacity(l.len() * 2), |mut s, c| {
-                    s.push_str(match c {
-                        'O' => "[]",
-                        '@' => "@.",
-                        '.' => "..",
-                        '#' => "##",
-                        _ => unreachable!(),
-                    });
-                    s
-                })
-        })
-        .fold(String::with_capacity(input.len() * 2), |s, l| {
-            format!("{}{}\n", s, l)
-        })
+        .take_while(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
         .as_str()
         .into();
     let moves = input
         .lines()
-        .skip_while(|l| *l != "")
+        .skip_while(|l| !l.is_empty())
         .flat_map(|l| l.chars().map(|c| Direction::from_arrow(c).unwrap()))
         .collect();
 
-    (map, map2, moves)
+    (map, moves)
 }
 
-pub fn part1((map, _, moves): &Input) -> u32 {
-    let mut map = map.clone();
-
-    let mut pos = map.find(P1Cell::Robot).unwrap();
-    map.set(pos, P1Cell::Empty);
-
-    for d in moves.iter().cloned() {
-        let new_pos = pos.moved_in(d);
-
-        match map.get(new_pos).unwrap() {
-            P1Cell::Empty => pos = new_pos,
-            P1Cell::Box => {
-                let mut next_empty_pos = pos.moved_in(d);
-                loop {
-                    match map.get(next_empty_pos).unwrap() {
-                        P1Cell::Empty => {
-                            map.set(new_pos, P1Cell::Empty);
-                            map.set(next_empty_pos, P1Cell::Box);
-                            pos = new_pos;
-                            break;
-                        }
-                        P1Cell::Box => {
-                            next_empty_pos = next_empty_pos.moved_in(d);
-                        }
-                        P1Cell::Wall => {
-                            break;
-                        }
-                        P1Cell::Robot => unreachable!(),
-                    }
-                }
-            }
-            P1Cell::Wall => {}
-            P1Cell::Robot => unreachable!(),
-        }
-    }
+pub fn part1((map, moves): &Input) -> u64 {
+    let mut warehouse = Warehouse::new(map, &Config::PART1);
+    warehouse.run(moves);
+    warehouse.gps_sum()
+}
 
-    map.iter()
-        .map(|(p, c)| {
-            if matches!(c, P1Cell::Box) {
-                100 * p.row as u32 + p.col as u32
-            } else {
-                0
-            }
-        })
-        .sum::<u32>()
+pub fn part2((map, moves): &Input) -> u64 {
+    let mut warehouse = Warehouse::new(map, &Config::PART2);
+    warehouse.run(moves);
+    warehouse.gps_sum()
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum P1Cell {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tile {
     Wall,
     Box,
     Robot,
     Empty,
 }
 
-impl From<char> for P1Cell {
+impl From<char> for Tile {
     fn from(value: char) -> Self {
         match value {
-            '#' => P1Cell::Wall,
-            'O' => P1Cell::Box,
-            '@' => P1Cell::Robot,
-            '.' => P1Cell::Empty,
+            '#' => Tile::Wall,
+            'O' => Tile::Box,
+            '@' => Tile::Robot,
+            '.' => Tile::Empty,
             _ => unreachable!(),
         }
     }
 }
 
-pub fn part2((_, map, moves): &Input) -> u32 {
-    let mut map = map.clone();
-
-    let mut pos = map.find(P2Cell::Robot).unwrap();
-    map.set(pos, P2Cell::Empty);
-
-    for d in moves.iter().cloned() {
-        let new_pos = pos.moved_in(d);
-
-        match map.get(new_pos).unwrap() {
-            P2Cell::Empty => pos = new_pos,
-            P2Cell::LBox | P2Cell::RBox => {
-                if matches!(d, Direction::Left | Direction::Right) {
-                    if try_push_horizontal(&mut map, new_pos, d) {
-                        pos = new_pos;
-                    }
-                } else {
-                    if test_push_vertical(&map, new_pos, d) {
-                        push_vertical(&mut map, new_pos, d);
-                        pos = new_pos;
-                    }
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// How many rows and columns each tile of the map turns into.
+    /// Boxes fill the whole scaled tile, the robot only its top-left cell.
+    pub scale: (i32, i32),
+    pub gps: fn(&Warehouse, &Entity) -> u64,
+}
+
+impl Config {
+    pub const PART1: Config = Config {
+        scale: (1, 1),
+        gps: gps_top_left,
+    };
+    pub const PART2: Config = Config {
+        scale: (1, 2),
+        ..Config::PART1
+    };
+}
+
+/// The puzzle's GPS coordinate, from the top and left edges of the map to the box's top-left corner.
+pub fn gps_top_left(_: &Warehouse, entity: &Entity) -> u64 {
+    100 * entity.pos.row as u64 + entity.pos.col as u64
+}
+
+/// A box, as a rectangle of cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entity {
+    /// Top-left cell.
+    pub pos: Position,
+    pub height: i32,
+    pub width: i32,
+}
+
+impl Entity {
+    pub fn cells(&self) -> impl Iterator<Item = Position> + '_ {
+        (0..self.height)
+            .flat_map(move |row| (0..self.width).map(move |col| self.pos + Position::new(row, col)))
+    }
+}
+
+#[derive(Clone)]
+pub struct Warehouse {
+    walls: Grid<bool>,
+    boxes: Vec<Entity>,
+    /// Which box, if any, covers each cell.
+    occupancy: Grid<Option<usize>>,
+    robot: Position,
+    gps: fn(&Warehouse, &Entity) -> u64,
+}
+
+impl Warehouse {
+    pub fn new(map: &Grid<Tile>, config: &Config) -> Self {
+        let (height, width) = config.scale;
+        let (n_rows, n_cols) = (map.n_rows() * height, map.n_cols() * width);
+
+        let mut warehouse = Warehouse {
+            walls: Grid::new(n_rows as usize, n_cols as usize, false),
+            boxes: Vec::new(),
+            occupancy: Grid::new(n_rows as usize, n_cols as usize, None),
+            robot: Position::new(0, 0),
+            gps: config.gps,
+        };
+
+        for (pos, tile) in map.iter() {
+            let entity = Entity {
+                pos: Position::new(pos.row * height, pos.col * width),
+                height,
+                width,
+            };
+            match tile {
+                Tile::Wall => entity.cells().for_each(|c| warehouse.walls.set(c, true)),
+                Tile::Box => {
+                    warehouse.boxes.push(entity);
+                    warehouse.place(warehouse.boxes.len() - 1);
                 }
+                Tile::Robot => warehouse.robot = entity.pos,
+                Tile::Empty => {}
             }
-            P2Cell::Wall => {}
-            P2Cell::Robot => unreachable!(),
         }
+
+        warehouse
     }
 
-    map.iter()
-        .map(|(p, c)| {
-            if matches!(c, P2Cell::LBox) {
-                100 * p.row as u32 + p.col as u32
-            } else {
-                0
-            }
-        })
-        .sum::<u32>()
-}
+    /// Anything off the map counts as wall.
+    pub fn is_wall(&self, pos: Position) -> bool {
+        self.walls.get(pos).is_none_or(|w| *w)
+    }
+
+    pub fn box_at(&self, pos: Position) -> Option<usize> {
+        self.occupancy.get(pos).copied().flatten()
+    }
 
-fn try_push_horizontal(map: &mut Grid<P2Cell>, box_position: Position, d: Direction) -> bool {
-    let mut next_free_pos = box_position.moved_in(d);
-    loop {
-        match map.get(next_free_pos).unwrap() {
-            P2Cell::Wall => {
-                return false;
+    fn place(&mut self, id: usize) {
+        for c in self.boxes[id].cells() {
+            self.occupancy.set(c, Some(id));
+        }
+    }
+
+    fn lift(&mut self, id: usize) {
+        for c in self.boxes[id].cells() {
+            self.occupancy.set(c, None);
+        }
+    }
+
+    /// Moves the robot one step, pushing whatever boxes are in the way.
+    /// Returns the boxes that moved, or `None` if something hit a wall and nothing moved.
+    pub fn push(&mut self, d: Direction) -> Option<Vec<usize>> {
+        let target = self.robot.moved_in(d);
+
+        // Everything touching the front of something already being pushed gets pushed too.
+        let mut pushed = Vec::new();
+        let mut frontier = vec![target];
+        while let Some(cell) = frontier.pop() {
+            if self.is_wall(cell) {
+                return None;
             }
-            P2Cell::LBox | P2Cell::RBox => {}
-            P2Cell::Empty => {
-                map.set(box_position, P2Cell::Empty);
-                let mut next_swap_pos = box_position.moved_in(d);
-                while next_swap_pos != next_free_pos {
-                    map.set(
-                        next_swap_pos,
-                        match map.get(next_swap_pos).unwrap() {
-                            P2Cell::LBox => P2Cell::RBox,
-                            P2Cell::RBox => P2Cell::LBox,
-                            _ => unreachable!(),
-                        },
-                    );
-
-                    next_swap_pos = next_swap_pos.moved_in(d);
-                }
-                map.set(
-                    next_swap_pos,
-                    if d == Direction::Right {
-                        P2Cell::RBox
-                    } else {
-                        P2Cell::LBox
-                    },
-                );
-                return true;
+            let Some(id) = self.box_at(cell) else {
+                continue;
+            };
+            if pushed.contains(&id) {
+                continue;
             }
-            P2Cell::Robot => unreachable!(),
+
+            pushed.push(id);
+            frontier.extend(
+                self.boxes[id]
+                    .cells()
+                    .map(|c| c.moved_in(d))
+                    .filter(|c| self.box_at(*c) != Some(id)),
+            );
         }
-        next_free_pos = next_free_pos.moved_in(d);
+
+        self.shift(&pushed, d);
+        self.robot = target;
+
+        Some(pushed)
     }
-}
 
-fn test_push_vertical(map: &Grid<P2Cell>, box_position: Position, d: Direction) -> bool {
-    let (l_pos, r_pos) = match map.get(box_position).unwrap() {
-        P2Cell::LBox => (box_position, box_position.moved_in(Direction::Right)),
-        P2Cell::RBox => (box_position.moved_in(Direction::Left), box_position),
-        _ => unreachable!(),
-    };
+    /// Moves boxes without checking whether there's room.
+    fn shift(&mut self, ids: &[usize], d: Direction) {
+        // Lift everything first so boxes don't overwrite each other.
+        for id in ids {
+            self.lift(*id);
+        }
+        for id in ids {
+            self.boxes[*id].pos = self.boxes[*id].pos.moved_in(d);
+            self.place(*id);
+        }
+    }
 
-    match map.get(l_pos.moved_in(d)).unwrap() {
-        P2Cell::Wall => return false,
-        P2Cell::Empty => {}
-        _ => {
-            if !test_push_vertical(map, l_pos.moved_in(d), d) {
-                return false;
+    pub fn run(&mut self, moves: &[Direction]) {
+        for d in moves {
+            self.push(*d);
+        }
+    }
+
+    pub fn gps_sum(&self) -> u64 {
+        self.boxes.iter().map(|b| (self.gps)(self, b)).sum()
+    }
+
+    /// One line per row, `O` for single-cell boxes and `[]` or `[==]` rows for wider ones.
+    pub fn render(&self) -> String {
+        self.walls.render(|pos, _| self.symbol(pos))
+    }
+
+    /// Like `render`, but coloured for a terminal, making the boxes in `highlight` stand out.
+    pub fn render_ansi(&self, highlight: &[usize]) -> String {
+        self.walls.render(|pos, _| {
+            let c = self.symbol(pos);
+            match c {
+                '@' => format!("\x1b[1;33m{c}\x1b[0m"),
+                '#' => format!("\x1b[90m{c}\x1b[0m"),
+                '.' => c.to_string(),
+                _ if highlight.contains(&self.box_at(pos).unwrap()) => {
+                    format!("\x1b[1;32m{c}\x1b[0m")
+                }
+                _ => format!("\x1b[32m{c}\x1b[0m"),
             }
+        })
+    }
+
+    fn symbol(&self, pos: Position) -> char {
+        if self.is_wall(pos) {
+            '#'
+        } else if pos == self.robot {
+            '@'
+        } else if let Some(id) = self.box_at(pos) {
+            let b = &self.boxes[id];
+            match (b.width, pos.col - b.pos.col) {
+                (1, _) => 'O',
+                (_, 0) => '[',
+                (w, x) if x == w - 1 => ']',
+                _ => '=',
+            }
+        } else {
+            '.'
         }
     }
-    match map.get(r_pos.moved_in(d)).unwrap() {
-        P2Cell::Wall => return false,
-        P2Cell::Empty => {}
-        _ => {
-            if !test_push_vertical(map, r_pos.moved_in(d), d) {
-                return false;
+
+    /// Checks the things no push should change: the walls, the number of boxes,
+    /// and that nothing overlaps.
+    pub fn check_against(&self, initial: &Warehouse) -> Result<(), String> {
+        if !self.walls.iter().eq(initial.walls.iter()) {
+            return Err("walls changed".to_string());
+        }
+        if self.boxes.len() != initial.boxes.len() {
+            return Err(format!(
+                "{} boxes, expected {}",
+                self.boxes.len(),
+                initial.boxes.len()
+            ));
+        }
+
+        for (id, b) in self.boxes.iter().enumerate() {
+            if let Some(c) = b
+                .cells()
+                .find(|c| self.is_wall(*c) || self.box_at(*c) != Some(id))
+            {
+                return Err(format!("box {id} at {:?} isn't alone at {c:?}", b.pos));
             }
         }
+        let covered = self.occupancy.iter().filter(|(_, id)| id.is_some()).count();
+        let area = self
+            .boxes
+            .iter()
+            .map(|b| (b.width * b.height) as usize)
+            .sum::<usize>();
+        if covered != area {
+            return Err(format!(
+                "{covered} cells covered by boxes of total area {area}"
+            ));
+        }
+
+        if self.is_wall(self.robot) || self.box_at(self.robot).is_some() {
+            return Err(format!("robot at {:?} is inside something", self.robot));
+        }
+
+        Ok(())
     }
+}
 
-    true
+// For inspecting the warehouse, e.g. from a custom score.
+impl Warehouse {
+    #[allow(dead_code)]
+    pub fn robot(&self) -> Position {
+        self.robot
+    }
+
+    #[allow(dead_code)]
+    pub fn boxes(&self) -> &[Entity] {
+        &self.boxes
+    }
+
+    #[allow(dead_code)]
+    pub fn n_rows(&self) -> i32 {
+        self.walls.n_rows()
+    }
+
+    #[allow(dead_code)]
+    pub fn n_cols(&self) -> i32 {
+        self.walls.n_cols()
+    }
 }
 
-/// Assumes a test_push_vertical on the same box_position returned true
-fn push_vertical(map: &mut Grid<P2Cell>, box_position: Position, d: Direction) {
-    let (l_pos, r_pos) = match map.get(box_position).unwrap() {
-        P2Cell::LBox => (box_position, box_position.moved_in(Direction::Right)),
-        P2Cell::RBox => (box_position.moved_in(Direction::Left), box_position),
-        _ => unreachable!(),
-    };
+/// What one move did, enough to undo it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub dir: Direction,
+    pub robot_from: Position,
+    /// Empty if the robot walked into free space, `None` if it was blocked.
+    pub moved: Option<Vec<usize>>,
+}
+
+/// Steps through a warehouse one move at a time, keeping a log that can be undone and redone.
+///
+/// `check` compares the warehouse against the one it started as, which scans the whole grid.
+/// A `checked` replay does that after every step, undo and redo, and fails the first one
+/// that breaks something. The step is still applied and logged so it can be looked at.
+pub struct Replay {
+    initial: Warehouse,
+    warehouse: Warehouse,
+    log: Vec<Step>,
+    /// Undone steps, most recently undone last.
+    redo: Vec<Step>,
+    checked: bool,
+}
 
-    match map.get(l_pos.moved_in(d)).unwrap() {
-        P2Cell::LBox | P2Cell::RBox => {
-            push_vertical(map, l_pos.moved_in(d), d);
+impl Replay {
+    pub fn new(warehouse: Warehouse) -> Self {
+        Replay {
+            initial: warehouse.clone(),
+            warehouse,
+            log: Vec::new(),
+            redo: Vec::new(),
+            checked: false,
         }
-        P2Cell::Empty => {}
-        _ => unreachable!(),
     }
-    match map.get(r_pos.moved_in(d)).unwrap() {
-        P2Cell::LBox | P2Cell::RBox => {
-            push_vertical(map, r_pos.moved_in(d), d);
+
+    pub fn checked(warehouse: Warehouse) -> Self {
+        Replay {
+            checked: true,
+            ..Replay::new(warehouse)
         }
-        P2Cell::Empty => {}
-        _ => unreachable!(),
     }
 
-    map.set(l_pos, P2Cell::Empty);
-    map.set(l_pos.moved_in(d), P2Cell::LBox);
+    pub fn warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
 
-    map.set(r_pos, P2Cell::Empty);
-    map.set(r_pos.moved_in(d), P2Cell::RBox);
+    pub fn log(&self) -> &[Step] {
+        &self.log
+    }
+
+    /// Applies a move, forgetting anything that could have been redone.
+    pub fn step(&mut self, dir: Direction) -> Result<&Step, String> {
+        let robot_from = self.warehouse.robot;
+        let moved = self.warehouse.push(dir);
+        self.redo.clear();
+        self.record(Step {
+            dir,
+            robot_from,
+            moved,
+        })
+    }
+
+    /// `None` if there's nothing to undo.
+    pub fn undo(&mut self) -> Result<Option<&Step>, String> {
+        let Some(step) = self.log.pop() else {
+            return Ok(None);
+        };
+        if let Some(moved) = &step.moved {
+            self.warehouse.shift(moved, step.dir.reversed());
+            self.warehouse.robot = step.robot_from;
+        }
+        self.redo.push(step);
+        self.check_if_checked()?;
+        Ok(self.redo.last())
+    }
+
+    /// `None` if there's nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<&Step>, String> {
+        let Some(step) = self.redo.pop() else {
+            return Ok(None);
+        };
+        if let Some(moved) = &step.moved {
+            self.warehouse.shift(moved, step.dir);
+            self.warehouse.robot = step.robot_from.moved_in(step.dir);
+        }
+        self.record(step).map(Some)
+    }
+
+    fn record(&mut self, step: Step) -> Result<&Step, String> {
+        self.log.push(step);
+        self.check_if_checked()?;
+        Ok(self.log.last().unwrap())
+    }
+
+    fn check_if_checked(&self) -> Result<(), String> {
+        if self.checked {
+            self.check()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `Warehouse::check_against` the starting warehouse, with the step count and a picture.
+    pub fn check(&self) -> Result<(), String> {
+        self.warehouse.check_against(&self.initial).map_err(|e| {
+            format!(
+                "after {} steps: {e}\n{}",
+                self.log.len(),
+                self.warehouse.render()
+            )
+        })
+    }
+
+    /// Plays `moves` as a terminal animation, each frame redrawing in place over the last.
+    /// Stops at the first step that fails.
+    pub fn animate(
+        &mut self,
+        moves: &[Direction],
+        mut on_frame: impl FnMut(&str),
+    ) -> Result<(), String> {
+        on_frame(&format!("\x1b[2J\x1b[H{}", self.warehouse.render_ansi(&[])));
+        for (i, d) in moves.iter().enumerate() {
+            let moved = self.step(*d)?.moved.clone().unwrap_or_default();
+            on_frame(&format!(
+                "\x1b[H{}move {} of {}: {}\n",
+                self.warehouse.render_ansi(&moved),
+                i + 1,
+                moves.len(),
+                d.to_arrow()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Plays `moves`, rendering the frames `keep` picks out by step number.
+    /// Frame 0 is the warehouse before any of them.
+    #[allow(dead_code)]
+    pub fn frames(
+        &mut self,
+        moves: &[Direction],
+        keep: impl Fn(usize) -> bool,
+    ) -> Result<Vec<String>, String> {
+        let mut frames = Vec::new();
+        if keep(0) {
+            frames.push(self.warehouse.render());
+        }
+        for (i, d) in moves.iter().enumerate() {
+            self.step(*d)?;
+            if keep(i + 1) {
+                frames.push(self.warehouse.render());
+            }
+        }
+        Ok(frames)
+    }
 }
 
-#[derive(Clone, PartialEq)]
-pub enum P2Cell {
-    Wall,
-    LBox,
-    RBox,
-    Robot,
-    Empty,
+/// Steps through an input from stdin, for `day15-replay`.
+/// Enter steps once and a number steps that many times, `u` and `r` undo and redo,
+/// `a` animates the rest of the moves and `q` quits.
+/// Every step is checked, stopping a batch at the first one that breaks something.
+pub fn replay((map, moves): &Input, config: &Config) {
+    let mut replay = Replay::checked(Warehouse::new(map, config));
+    print!("\x1b[2J\x1b[H{}", replay.warehouse().render_ansi(&[]));
+
+    for line in std::io::stdin().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let remaining = &moves[replay.log().len()..];
+        let result = match line.trim() {
+            "q" => break,
+            "u" => replay.undo().map(|_| ()),
+            "r" => replay.redo().map(|_| ()),
+            "a" => replay.animate(remaining, |frame| {
+                print!("{}", frame);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }),
+            n => match if n.is_empty() { Ok(1) } else { n.parse() } {
+                Ok(n) => remaining
+                    .iter()
+                    .take(n)
+                    .try_for_each(|d| replay.step(*d).map(|_| ())),
+                Err(_) => {
+                    println!("expected a number of steps, u, r, a or q");
+                    Ok(())
+                }
+            },
+        };
+
+        let moved = replay.log().last().and_then(|s| s.moved.clone());
+        print!(
+            "\x1b[H{}",
+            replay.warehouse().render_ansi(&moved.unwrap_or_default())
+        );
+        println!(
+            "{} of {} moves, GPS sum {}\x1b[K",
+            replay.log().len(),
+            moves.len(),
+            replay.warehouse().gps_sum()
+        );
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+    }
 }
 
-impl From<char> for P2Cell {
-    fn from(value: char) -> Self {
-        match value {
-            '#' => P2Cell::Wall,
-            '[' => P2Cell::LBox,
-            ']' => P2Cell::RBox,
-            '@' => P2Cell::Robot,
-            '.' => P2Cell::Empty,
-            _ => unreachable!(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_EXAMPLE: &str = "########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<";
+
+    const EXAMPLE: &str = "##########
+#..O..O.O#
+#......O.#
+#.OO..O.O#
+#..O@..O.#
+#O#..O...#
+#O..O..O.#
+#.OO.O.OO#
+#....O...#
+##########
+
+<vv>^<v^>v>^vv^v>v<>v^v<v<^vv<<<^><<><>>v<vvv<>^v^>^<<<><<v<<<v^vv^v>^
+vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
+><>vv>v^v^<>><>>>><^^>vv>v<^^^>>v^v^<^^>v^^>v^<^v>v<>>v^v^<v>v^^<^^vv<
+<<v<^>>^^^^>>>v^<>vvv^><v<<<>^^^vv^<vvv>^>v<^^^^v<>^>vvvv><>>v^<<^^^^^
+^><^><>>><>^^<<^^v>>><^<v>^<vv>>v>>>^v><>^v><<<<v>>v<v<v>vvv>^<><<>^><
+^>><>^v<><^vvv<^^<><v<<<<<><^v<<<><<<^^<v<^^^><^>>^<v^><<<^>>^v<v^v<v^
+>^>>^v>vv>^<<^v<>><<><<v<<v><>v<^vv<<<>^^v^>^^>>><<^v>>v^v><^^>>^<>vv^
+<><^^>^^^<><vvvvv^v<v<<>^v<v>v<<^><<><<><<<^^<<<^<<>><<><^^^>^^<>^>v<>
+^^>vv<^v^v<vv>^<><v<^v>^^^>>>^^vvv^>vvv<>>>^<^>>>>>^<<^v>^vvv<>^<><<v>
+v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(SMALL_EXAMPLE)), 2028);
+        assert_eq!(part1(&parse(EXAMPLE)), 10092);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 9021);
+    }
+
+    #[test]
+    fn test_push() {
+        let (map, _) = parse("#######\n#.....#\n#..O..#\n#..O..#\n#..@..#\n#######\n\n");
+
+        // Pushing a stack of wide boxes up moves both, then they hit the wall.
+        let mut warehouse = Warehouse::new(&map, &Config::PART2);
+        assert_eq!(warehouse.push(Direction::Up), Some(vec![1, 0]));
+        assert_eq!(warehouse.push(Direction::Up), None);
+        assert_eq!(warehouse.robot(), Position::new(3, 6));
+        assert_eq!(warehouse.boxes()[0].pos, Position::new(1, 6));
+
+        // Tall and wide boxes, and a custom score.
+        let config = Config {
+            scale: (2, 2),
+            gps: |w, b| (w.n_rows() - b.pos.row) as u64,
+        };
+        let mut warehouse = Warehouse::new(&map, &config);
+        assert_eq!((warehouse.n_rows(), warehouse.n_cols()), (12, 14));
+        assert_eq!(warehouse.push(Direction::Up), Some(vec![1, 0]));
+        assert_eq!(warehouse.box_at(Position::new(3, 7)), Some(0));
+        assert_eq!(warehouse.box_at(Position::new(5, 6)), Some(1));
+        assert_eq!(warehouse.box_at(Position::new(7, 6)), None);
+        assert_eq!(warehouse.gps_sum(), (12 - 3) + (12 - 5));
+    }
+
+    #[test]
+    fn test_replay() {
+        let (map, moves) = parse(SMALL_EXAMPLE);
+        let mut replay = Replay::checked(Warehouse::new(&map, &Config::PART1));
+
+        for d in &moves {
+            replay.step(*d).unwrap();
         }
+        assert_eq!(replay.warehouse().gps_sum(), 2028);
+        assert_eq!(
+            replay.warehouse().render(),
+            "########\n#....OO#\n##.....#\n#.....O#\n#.#O@..#\n#...O..#\n#...O..#\n########\n"
+        );
+
+        // The first move is into a wall.
+        assert_eq!(replay.log()[0].moved, None);
+
+        // All the way back and forward again.
+        while replay.undo().unwrap().is_some() {}
+        assert_eq!(
+            replay.warehouse().render(),
+            SMALL_EXAMPLE.split("\n\n").next().unwrap().to_string() + "\n"
+        );
+        while replay.redo().unwrap().is_some() {}
+        assert_eq!(replay.warehouse().gps_sum(), 2028);
+        assert_eq!(replay.check(), Ok(()));
+
+        // Stepping drops the redo history.
+        replay.undo().unwrap();
+        replay.step(Direction::Up).unwrap();
+        assert_eq!(replay.redo(), Ok(None));
+    }
+
+    #[test]
+    fn test_frames() {
+        let (map, moves) = parse(EXAMPLE);
+        let mut replay = Replay::new(Warehouse::new(&map, &Config::PART2));
+
+        let frames = replay
+            .frames(&moves, |i| i == 0 || i == moves.len())
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            frames[1],
+            "####################
+##[].......[].[][]##
+##[]...........[].##
+##[]........[][][]##
+##[]......[]....[]##
+##..##......[]....##
+##..[]............##
+##..@......[].[][]##
+##......[][]..[]..##
+####################
+"
+        );
+
+        let mut replay = Replay::new(Warehouse::new(&map, &Config::PART2));
+        let mut animation = String::new();
+        replay
+            .animate(&moves[..3], |frame| animation.push_str(frame))
+            .unwrap();
+        assert_eq!(animation.matches("\x1b[H").count(), 4);
+        assert!(animation.ends_with("move 3 of 3: v\n"));
+    }
+
+    #[test]
+    fn test_check() {
+        let (map, _) = parse(SMALL_EXAMPLE);
+        let broken = |replay: &mut Replay| replay.warehouse.walls.set(Position::new(1, 1), true);
+
+        // Only checked on demand.
+        let mut replay = Replay::new(Warehouse::new(&map, &Config::PART1));
+        replay.step(Direction::Down).unwrap();
+        broken(&mut replay);
+        replay.step(Direction::Down).unwrap();
+        let e = replay.check().unwrap_err();
+        assert!(e.starts_with("after 2 steps: walls changed\n"));
+
+        // Caught on the step it happens.
+        let mut replay = Replay::checked(Warehouse::new(&map, &Config::PART1));
+        replay.step(Direction::Down).unwrap();
+        broken(&mut replay);
+        let e = replay.step(Direction::Down).unwrap_err();
+        assert!(e.starts_with("after 2 steps: walls changed\n"));
+        assert_eq!(replay.log().len(), 2);
+        assert!(replay.undo().is_err());
     }
 }
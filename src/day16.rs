@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::{cmp::Reverse, collections::BinaryHeap};
 
 use crate::util::{
     grid::Grid,
@@ -32,145 +32,226 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u64 {
-    let start = input.find(Cell::Start).unwrap();
-
-    let mut visited = HashSet::new();
-
-    let mut q = Vec::new();
-    q.push(Entry {
-        pos: start,
-        dir: Direction::Right,
-        score: 0,
-    });
-
-    while let Some(Entry { pos, dir, score }) = q.pop() {
-        let ve = VisitedEntry { pos, dir };
-        if visited.contains(&ve) {
-            continue;
-        }
-        visited.insert(ve);
+    best_paths(input, &Costs::PUZZLE).unwrap().score
+}
 
-        if *input.get(pos).unwrap() == Cell::End {
-            return score;
-        }
+pub fn part2(input: &Input) -> u64 {
+    best_paths(input, &Costs::PUZZLE).unwrap().count() as u64
+}
 
-        for new_dir in Direction::ALL {
-            if new_dir == dir.reversed() {
-                continue;
-            }
+#[derive(Clone, Copy, Debug)]
+pub struct Costs {
+    /// Moving forward one tile.
+    pub step: u64,
+    /// Turning a quarter turn on the spot.
+    pub turn: u64,
+}
 
-            let new_pos = pos.moved_in(new_dir);
-            if *input.get(new_pos).unwrap() == Cell::Wall {
-                continue;
-            }
+impl Costs {
+    pub const PUZZLE: Costs = Costs {
+        step: 1,
+        turn: 1000,
+    };
+}
 
-            let new_score = score + if new_dir != dir { 1001 } else { 1 };
+/// Every tile on at least one cheapest path from start to end.
+pub struct BestPaths {
+    pub score: u64,
+    tiles: Grid<bool>,
+}
 
-            q.push(Entry {
-                pos: new_pos,
-                dir: new_dir,
-                score: new_score,
-            });
-        }
+impl BestPaths {
+    pub fn count(&self) -> usize {
+        self.tiles.iter().filter(|(_, on_path)| *on_path).count()
+    }
 
-        // Reverse so min at end for pop()
-        q.sort_by(|a, b| a.score.cmp(&b.score).reverse());
+    #[allow(dead_code)]
+    pub fn contains(&self, pos: Position) -> bool {
+        self.tiles.is(pos, true)
     }
 
-    unreachable!("didn't find the end")
+    /// The maze with best path tiles drawn as `O`, like in the puzzle.
+    #[allow(dead_code)]
+    pub fn render(&self, maze: &Grid<Cell>) -> String {
+        maze.render(|pos, cell| match cell {
+            Cell::Start => 'S',
+            Cell::End => 'E',
+            Cell::Wall => '#',
+            Cell::Empty if self.contains(pos) => 'O',
+            Cell::Empty => '.',
+        })
+    }
 }
 
-pub fn part2(input: &Input) -> u64 {
-    let start = input.find(Cell::Start).unwrap();
-
-    let mut visited = HashSet::new();
-
-    let mut prevs = HashMap::new();
-    prevs.insert(
-        PreviousKey {
-            pos: start,
-            score: 0,
-        },
-        HashSet::from([start]),
-    );
-
-    let mut q = Vec::new();
-    q.push(Entry {
-        pos: start,
-        dir: Direction::Right,
-        score: 0,
-    });
-
-    while let Some(Entry { pos, dir, score }) = q.pop() {
-        let ve = VisitedEntry { pos, dir };
-        if visited.contains(&ve) {
+/// Dijkstra over (tile, facing) states, starting at `S` facing east.
+/// Keeps every equally cheap way into each state, so the optimal paths
+/// can be walked back from the end afterwards.
+pub fn best_paths(maze: &Grid<Cell>, costs: &Costs) -> Option<BestPaths> {
+    let start = maze.find(Cell::Start)?;
+    let end = maze.find(Cell::End)?;
+
+    let n_cols = maze.n_cols();
+    let index = |pos: Position, dir: Direction| {
+        (pos.row * n_cols + pos.col) as usize * 4
+            + Direction::ALL.iter().position(|d| *d == dir).unwrap()
+    };
+    let state = |i: usize| {
+        let tile = (i / 4) as i32;
+        (
+            Position::new(tile / n_cols, tile % n_cols),
+            Direction::ALL[i % 4],
+        )
+    };
+
+    let n_states = (maze.n_rows() * n_cols) as usize * 4;
+    let mut dist = vec![u64::MAX; n_states];
+    let mut predecessors = vec![Vec::new(); n_states];
+    let mut q = BinaryHeap::new();
+
+    let first = index(start, Direction::Right);
+    dist[first] = 0;
+    q.push(Reverse((0, first)));
+
+    while let Some(Reverse((score, i))) = q.pop() {
+        if score > dist[i] {
             continue;
         }
-        visited.insert(ve);
 
-        if *input.get(pos).unwrap() == Cell::End {
-            break;
-        }
-
-        for new_dir in Direction::ALL {
-            if new_dir == dir.reversed() {
+        let (pos, dir) = state(i);
+        let ahead = pos.moved_in(dir);
+        let moves = [
+            (ahead, dir, costs.step),
+            (pos, dir.rotated_clockwise(), costs.turn),
+            (pos, dir.rotated_anticlockwise(), costs.turn),
+        ];
+        for (new_pos, new_dir, cost) in moves {
+            if maze.get(new_pos).is_none_or(|c| *c == Cell::Wall) {
                 continue;
             }
 
-            let new_pos = pos.moved_in(new_dir);
-            if *input.get(new_pos).unwrap() == Cell::Wall {
-                continue;
+            let j = index(new_pos, new_dir);
+            let new_score = score + cost;
+            if new_score < dist[j] {
+                dist[j] = new_score;
+                predecessors[j] = vec![i];
+                q.push(Reverse((new_score, j)));
+            } else if new_score == dist[j] {
+                predecessors[j].push(i);
             }
-
-            let new_score = score + if new_dir != dir { 1001 } else { 1 };
-
-            q.push(Entry {
-                pos: new_pos,
-                dir: new_dir,
-                score: new_score,
-            });
-
-            // Using remove() here avoids needing to clone the `ps` allocation to avoid having a & and &mut to prevs at the same time
-            // (without using Cell/RefCell/etc. which I cba)
-            // Doesn't actually save much performance, oh well
-            let ps = prevs.remove(&PreviousKey { pos, score }).unwrap();
-            prevs
-                .entry(PreviousKey {
-                    pos: new_pos,
-                    score: new_score,
-                })
-                .or_insert(HashSet::from([new_pos]))
-                .extend(ps.iter());
-            prevs.insert(PreviousKey { pos, score }, ps);
         }
+    }
 
-        // Reverse so min at end for pop()
-        q.sort_by(|a, b| a.score.cmp(&b.score).reverse());
+    let score = Direction::ALL.iter().map(|d| dist[index(end, *d)]).min()?;
+    if score == u64::MAX {
+        return None;
     }
 
-    let end_pos = input.find(Cell::End).unwrap();
-    prevs
+    // Walk the predecessor DAG back from every cheapest way of arriving at the end.
+    let mut tiles = Grid::new(maze.n_rows() as usize, n_cols as usize, false);
+    let mut seen = vec![false; n_states];
+    let mut stack = Direction::ALL
         .iter()
-        .find(|(k, _)| k.pos == end_pos)
-        .unwrap()
-        .1
-        .len() as u64
-}
+        .map(|d| index(end, *d))
+        .filter(|i| dist[*i] == score)
+        .collect::<Vec<_>>();
+    while let Some(i) = stack.pop() {
+        if std::mem::replace(&mut seen[i], true) {
+            continue;
+        }
+        tiles.set(state(i).0, true);
+        stack.extend(&predecessors[i]);
+    }
 
-#[derive(PartialEq, Eq, Hash)]
-struct VisitedEntry {
-    pos: Position,
-    dir: Direction,
+    Some(BestPaths { score, tiles })
 }
 
-struct Entry {
-    pos: Position,
-    dir: Direction,
-    score: u64,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+
+    const EXAMPLE2: &str = "#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 7036);
+        assert_eq!(part1(&parse(EXAMPLE2)), 11048);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 45);
+        assert_eq!(part2(&parse(EXAMPLE2)), 64);
+    }
+
+    #[test]
+    fn test_render() {
+        let best = best_paths(&parse(EXAMPLE), &Costs::PUZZLE).unwrap();
+        assert_eq!(
+            best.render(&parse(EXAMPLE)),
+            "###############
+#.......#....E#
+#.#.###.#.###O#
+#.....#.#...#O#
+#.###.#####.#O#
+#.#.#.......#O#
+#.#.#####.###O#
+#..OOOOOOOOO#O#
+###O#O#####O#O#
+#OOO#O....#O#O#
+#O#O#O###.#O#O#
+#OOOOO#...#O#O#
+#O###.#.#.#O#O#
+#S..#.....#OOO#
+###############
+"
+        );
+    }
 
-#[derive(PartialEq, Eq, Hash)]
-struct PreviousKey {
-    pos: Position,
-    score: u64,
+    #[test]
+    fn test_costs() {
+        let maze = parse("#####\n#...#\n#S#E#\n#####");
+
+        // Up and over is the only way.
+        let best = best_paths(&maze, &Costs::PUZZLE).unwrap();
+        assert_eq!(best.score, 3004);
+        assert_eq!(best.count(), 5);
+
+        // Free turns leave just the steps.
+        let best = best_paths(&maze, &Costs { step: 1, turn: 0 }).unwrap();
+        assert_eq!(best.score, 4);
+
+        assert!(best_paths(&parse("####\n#S##\n##E#\n####"), &Costs::PUZZLE).is_none());
+    }
 }
@@ -1,47 +1,26 @@
+use crate::util::parse::{key_value, sections, unsigned_ints, Pattern};
+
 type Input = (Registers, Vec<u64>);
 
+const REGISTER: Pattern = Pattern::new("Register {}: {}");
+
 pub fn parse(input: &str) -> Input {
-    let mut ls = input.lines();
-
-    let a = ls
-        .next()
-        .unwrap()
-        .strip_prefix("Register A: ")
-        .unwrap()
-        .parse::<u64>()
-        .unwrap();
-    let b = ls
-        .next()
-        .unwrap()
-        .strip_prefix("Register B: ")
-        .unwrap()
-        .parse::<u64>()
-        .unwrap();
-    let c = ls
-        .next()
-        .unwrap()
-        .strip_prefix("Register C: ")
-        .unwrap()
-        .parse::<u64>()
-        .unwrap();
-
-    ls.next();
-    let program = ls
-        .next()
-        .unwrap()
-        .strip_prefix("Program: ")
-        .unwrap()
-        .chars()
-        .filter_map(|c| {
-            if c == ',' {
-                None
-            } else {
-                Some(c.to_digit(10).unwrap() as u64)
-            }
-        })
-        .collect();
+    let mut sections = sections(input);
+
+    let mut registers = Registers { a: 0, b: 0, c: 0 };
+    for (i, l) in sections.next().unwrap().lines().enumerate() {
+        let (name, value) = REGISTER.parse(l).map_err(|e| e.at_line(i + 1)).unwrap();
+        match name {
+            'A' => registers.a = value,
+            'B' => registers.b = value,
+            'C' => registers.c = value,
+            _ => unreachable!("unknown register {}", name),
+        }
+    }
+
+    let (_, program) = key_value(sections.next().unwrap()).unwrap();
 
-    (Registers { a, b, c }, program)
+    (registers, unsigned_ints(program).unwrap())
 }
 
 pub fn part1((registers, program): &Input) -> String {
@@ -218,3 +197,18 @@ enum InstructionResult {
     Output(u64),
     Jump(u64),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p1() {
+        let input = "Register A: 729
+Register B: 0
+Register C: 0
+
+Program: 0,1,5,4,3,0";
+        assert_eq!(part1(&parse(input)), "4,6,3,5,6,3,5,2,1,0");
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use crate::util::{
+    dsu::GridDsu,
     grid::Grid,
     position::{Direction, Position},
 };
@@ -29,44 +30,42 @@ pub fn part1(input: &Input) -> u64 {
     shortest_path(&map).unwrap()
 }
 
-pub fn part2(input: &Input) -> String {
-    let mut map = Grid::new(71, 71, Cell::Space);
-
-    let mut prev_applied = 0;
-
-    let mut lower = 0;
-    let mut upper = input.len();
+pub fn part2(input: &Input) -> Result<String, &'static str> {
+    // A byte falling somewhere already corrupted changes nothing, only the first one counts.
+    let mut seen = HashSet::new();
+    let bytes = input
+        .iter()
+        .filter(|p| seen.insert(**p))
+        .collect::<Vec<_>>();
 
-    loop {
-        let current_applied = (upper + lower) / 2;
+    // Start with every byte fallen and take them back out in reverse.
+    // The first one whose removal joins the corners up is the first one that cut them off.
+    let mut map = Grid::new(71, 71, Cell::Space);
+    for p in &bytes {
+        map.set(**p, Cell::Corrupted);
+    }
 
-        // Avoids cloning + applying from 0 on each iter.
-        if prev_applied < current_applied {
-            for p in input[prev_applied..=current_applied].iter() {
-                map.set(*p, Cell::Corrupted);
-            }
-        } else {
-            for p in input[current_applied + 1..=prev_applied].iter().rev() {
-                // By inspection the input does not contain duplicates so this is safe :)
-                map.set(*p, Cell::Space);
-            }
+    let mut dsu = GridDsu::new(map.n_rows(), map.n_cols());
+    for (p, c) in map.iter() {
+        if c == Cell::Space {
+            dsu.add(p);
         }
+    }
 
-        match shortest_path(&map) {
-            None => {
-                upper = current_applied;
-            }
-            Some(_) => {
-                lower = current_applied + 1;
+    let (start, end) = (Position::new(0, 0), Position::new(70, 70));
+    if dsu.connected(start, end) {
+        return Err("no byte cuts the exit off");
+    }
 
-                if lower == upper {
-                    return format!("{},{}", input[lower].row, input[lower].col);
-                }
-            }
-        }
+    for p in bytes.iter().rev() {
+        dsu.add(**p);
 
-        prev_applied = current_applied;
+        if dsu.connected(start, end) {
+            return Ok(format!("{},{}", p.row, p.col));
+        }
     }
+
+    Err("the exit can't be reached even before any bytes fall")
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -120,3 +119,26 @@ fn shortest_path(map: &Grid<Cell>) -> Option<u64> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p2() {
+        let bytes = |ps: &[(i32, i32)]| ps.iter().map(|&(r, c)| Position::new(r, c)).collect();
+
+        assert_eq!(
+            part2(&bytes(&[(0, 1), (5, 5), (1, 0)])),
+            Ok("1,0".to_string())
+        );
+        // Landing on the start cuts it off just the same.
+        assert_eq!(part2(&bytes(&[(3, 3), (0, 0)])), Ok("0,0".to_string()));
+        // The second (1, 0) doesn't cut anything off, it was already corrupted.
+        assert_eq!(
+            part2(&bytes(&[(1, 0), (0, 1), (1, 0)])),
+            Ok("0,1".to_string())
+        );
+        assert!(part2(&bytes(&[(5, 5), (6, 6)])).is_err());
+    }
+}
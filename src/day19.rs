@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::util::memo::Memo;
 
 type Input<'a> = (Vec<&'a str>, Vec<&'a str>);
 
@@ -12,7 +12,7 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1((patterns, designs): &Input) -> u64 {
-    let mut memo = HashMap::new();
+    let mut memo = Memo::new();
 
     designs
         .iter()
@@ -21,29 +21,21 @@ pub fn part1((patterns, designs): &Input) -> u64 {
 }
 
 fn is_design_possible<'a>(
-    memo: &mut HashMap<&'a str, bool>,
+    memo: &mut Memo<&'a str, bool>,
     patterns: &[&str],
     design: &'a str,
 ) -> bool {
-    if let Some(possible) = memo.get(design) {
-        return *possible;
-    }
-
-    for pattern in patterns {
-        if let Some(rest) = design.strip_prefix(pattern) {
-            if rest == "" || is_design_possible(memo, patterns, rest) {
-                memo.insert(design, true);
-                return true;
-            }
-        }
-    }
-
-    memo.insert(design, false);
-    return false;
+    memo.get_or_insert_with(design, |memo| {
+        patterns.iter().any(|pattern| {
+            design
+                .strip_prefix(pattern)
+                .is_some_and(|rest| rest.is_empty() || is_design_possible(memo, patterns, rest))
+        })
+    })
 }
 
 pub fn part2((patterns, designs): &Input) -> u64 {
-    let mut memo = HashMap::new();
+    let mut memo = Memo::new();
 
     designs
         .iter()
@@ -52,25 +44,48 @@ pub fn part2((patterns, designs): &Input) -> u64 {
 }
 
 fn count_ways_possible<'a>(
-    memo: &mut HashMap<&'a str, u64>,
+    memo: &mut Memo<&'a str, u64>,
     patterns: &[&str],
     design: &'a str,
 ) -> u64 {
-    if let Some(count) = memo.get(design) {
-        return *count;
-    }
-
-    let mut count = 0;
-    for pattern in patterns {
-        if let Some(rest) = design.strip_prefix(pattern) {
-            if rest == "" {
-                count += 1;
-            } else {
-                count += count_ways_possible(memo, patterns, rest);
+    memo.get_or_insert_with(design, |memo| {
+        let mut count = 0;
+        for pattern in patterns {
+            if let Some(rest) = design.strip_prefix(pattern) {
+                if rest.is_empty() {
+                    count += 1;
+                } else {
+                    count += count_ways_possible(memo, patterns, rest);
+                }
             }
         }
+
+        count
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "r, wr, b, g, bwu, rb, gb, br
+
+brwrr
+bggr
+gbbr
+rrbgbr
+ubwu
+bwurrg
+brgr
+bbrgwb";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 6);
     }
 
-    memo.insert(design, count);
-    return count;
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 16);
+    }
 }
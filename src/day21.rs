@@ -1,6 +1,9 @@
-use std::{collections::HashMap, fmt::Write};
+use std::fmt::Write;
 
-use crate::util::position::{Direction, Position};
+use crate::util::{
+    memo::Memo,
+    position::{Direction, Position},
+};
 
 type Input = Vec<Vec<NumpadButton>>;
 
@@ -22,7 +25,7 @@ pub fn part2(input: &Input) -> u64 {
 /// All of the code below this point is very ugly,
 /// but it works and is fast so idc :)
 fn score_input(input: &Input, depth: u64) -> u64 {
-    let mut cache = HashMap::new();
+    let mut cache = Memo::new();
 
     input
         .iter()
@@ -52,30 +55,27 @@ fn score_input(input: &Input, depth: u64) -> u64 {
 }
 
 fn score(
-    cache: &mut HashMap<(DPadButton, DPadButton, u64), u64>,
+    cache: &mut Memo<(DPadButton, DPadButton, u64), u64>,
     from: DPadButton,
     to: DPadButton,
     depth: u64,
 ) -> u64 {
-    if let Some(score) = cache.get(&(from, to, depth)) {
-        return *score;
-    }
-
-    let moves = dpad_moves_to_press(from, to);
+    cache.get_or_insert_with((from, to, depth), |cache| {
+        let moves = dpad_moves_to_press(from, to);
 
-    if depth == 1 {
-        return moves.len() as u64;
-    }
+        if depth == 1 {
+            return moves.len() as u64;
+        }
 
-    let mut calculated_score = 0;
-    let mut curr = DPadButton::A;
-    for d in moves {
-        calculated_score += score(cache, curr, d, depth - 1);
-        curr = d;
-    }
+        let mut calculated_score = 0;
+        let mut curr = DPadButton::A;
+        for d in moves {
+            calculated_score += score(cache, curr, d, depth - 1);
+            curr = d;
+        }
 
-    cache.insert((from, to, depth), calculated_score);
-    calculated_score
+        calculated_score
+    })
 }
 
 fn numpad_moves_to_press(from: NumpadButton, to: NumpadButton) -> Vec<DPadButton> {
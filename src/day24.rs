@@ -1,9 +1,11 @@
-use std::{collections::{HashMap, HashSet}, sync::LazyLock};
+use std::collections::{HashMap, HashSet};
 
-use regex::Regex;
+use crate::util::parse::{key_value, Pattern};
 
 type Input<'a> = (Vec<Wire>, Vec<&'a str>);
 
+const GATE: Pattern = Pattern::new("{} {} {} -> {}");
+
 pub fn parse(input: &str) -> Input {
     let mut name_to_id = HashMap::new();
     let mut flag = false;
@@ -14,9 +16,9 @@ pub fn parse(input: &str) -> Input {
         }
 
         let name = if !flag {
-            l.split(": ").next().unwrap()
+            key_value(l).unwrap().0
         } else {
-            l.split("-> ").skip(1).next().unwrap()
+            GATE.parse::<(&str, &str, &str, &str)>(l).unwrap().3
         };
 
         name_to_id.insert(name, id - if flag {1} else {0});
@@ -196,23 +198,14 @@ pub enum Wire {
 
 impl Wire {
     fn new_const<'a>(l: &'a str) -> Self {
-        let mut parts: std::str::Split<'_, &str> = l.split(": ");
-
-        let _name = parts.next().unwrap();
+        let (_name, value) = key_value(l).unwrap();
         
-        let value = parts.next().unwrap().parse::<u8>().unwrap() == 1;
+        let value = value.parse::<u8>().unwrap() == 1;
         Wire::Const(value)
     }
 
     fn new_operator<'a>(name_to_id: &mut HashMap<&'a str, usize>, l: &'a str) -> Self {
-        static R: LazyLock<Regex> = LazyLock::new(|| Regex::new("^([a-z\\d]{3}) (AND|OR|XOR) ([a-z\\d]{3}) -> ([a-z\\d]{3})$").unwrap());
-        
-        let captures = R.captures(l).unwrap();
-
-        let a = captures.get(1).unwrap().as_str();
-        let op = captures.get(2).unwrap().as_str();
-        let b = captures.get(3).unwrap().as_str();
-        let _c = captures.get(4).unwrap().as_str();
+        let (a, op, b, _c): (&str, &str, &str, &str) = GATE.parse(l).unwrap();
 
         let a_id = name_to_id.get(a).unwrap().clone();
         let b_id = name_to_id.get(b).unwrap().clone();
@@ -1,4 +1,7 @@
-use crate::util::grid::Grid;
+use crate::util::{
+    grid::Grid,
+    word_search::{find_word, Stencil},
+};
 
 pub type Input = Grid<char>;
 
@@ -7,53 +10,39 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u32 {
-    let mut count = 0;
-
-    for row in 0..input.n_rows() {
-        for col in 0..input.n_cols() {
-            if input.is_row_col(row, col, 'X') {
-                for dy in -1..1 {
-                    'dx: for dx in -1..1 {
-                        if dy == 0 && dx == 0 {
-                            continue;
-                        }
-
-                        for (step, c) in [(1, 'M'), (2, 'A'), (3, 'S')] {
-                            if !input.is_row_col(row + (dy * step), col + (dx * step), c) {
-                                continue 'dx;
-                            }
-                        }
-
-                        count += 1;
-                    }
-                }
-            }
-        }
-    }
-
-    count
+    find_word(input, "XMAS").len() as u32
 }
 
 pub fn part2(input: &Input) -> u32 {
-    let mut count = 0;
-
-    for row in 0..input.n_rows() {
-        for col in 0..input.n_cols() {
-            if input.is_row_col(row, col, 'A') {
-                if ((input.is_row_col(row - 1, col - 1, 'S')
-                    && input.is_row_col(row + 1, col + 1, 'M'))
-                    || (input.is_row_col(row - 1, col - 1, 'M')
-                        && input.is_row_col(row + 1, col + 1, 'S')))
-                    && ((input.is_row_col(row + 1, col - 1, 'S')
-                        && input.is_row_col(row - 1, col + 1, 'M'))
-                        || (input.is_row_col(row + 1, col - 1, 'M')
-                            && input.is_row_col(row - 1, col + 1, 'S')))
-                {
-                    count += 1;
-                }
-            }
-        }
+    Stencil::new("M.S\n.A.\nM.S")
+        .variants()
+        .iter()
+        .map(|s| s.find(input).len() as u32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 18);
     }
 
-    count
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 9);
+    }
 }
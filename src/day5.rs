@@ -1,4 +1,9 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::util::topo::{topological_sort, Cycle};
 
 type Input = (HashMap<u32, HashSet<u32>>, Vec<Vec<u32>>);
 
@@ -30,61 +35,155 @@ pub fn parse(input: &str) -> Input {
 pub fn part1((page_by_before_set, updates): &Input) -> u32 {
     updates
         .iter()
-        .map(|update| {
-            if is_correct(page_by_before_set, update) {
-                update[update.len() / 2]
-            } else {
-                0
-            }
-        })
+        .filter(|update| violations(page_by_before_set, update).is_empty())
+        .map(|update| update[update.len() / 2])
         .sum()
 }
 
-pub fn part2((page_by_before_set, updates): &Input) -> u32 {
+pub fn part2((page_by_before_set, updates): &Input) -> Result<u32, CycleReport> {
     updates
         .iter()
-        .filter(|update| !is_correct(page_by_before_set, update))
-        .cloned()
-        .map(|mut update| {
-            let mut done = false;
-            while !done {
-                done = true;
-
-                'outer: for i in 0..update.len() {
-                    let n = update[i];
-                    for j in i + 1..update.len() {
-                        let m = update[j];
-                        if let Some(before_set) = page_by_before_set.get(&m) {
-                            if before_set.contains(&n) {
-                                (update[i], update[j]) = (m, n);
-                                done = false;
-                                continue 'outer;
-                            }
-                        }
-                    }
-                }
-            }
-
-            update[update.len() / 2]
+        .filter(|update| !violations(page_by_before_set, update).is_empty())
+        .map(|update| {
+            let ordered =
+                order(page_by_before_set, update).map_err(|Cycle(cycle)| CycleReport {
+                    update: update.clone(),
+                    cycle,
+                })?;
+            Ok(ordered[ordered.len() / 2])
         })
         .sum()
 }
 
-fn is_correct(page_by_before_set: &HashMap<u32, HashSet<u32>>, update: &Vec<u32>) -> bool {
-    for (i, n) in update.iter().enumerate() {
-        if let Some(before_set) = page_by_before_set.get(n) {
-            for m in &update[..i] {
-                if before_set.contains(m) {
-                    return false;
-                }
-            }
-            for m in &update[i + 1..] {
-                if !before_set.contains(m) {
-                    return false;
-                }
+/// An update that can't be put in order because the rules between its pages loop.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CycleReport {
+    pub update: Vec<u32>,
+    pub cycle: Vec<u32>,
+}
+
+impl Display for CycleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |pages: &[u32]| {
+            pages
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        write!(
+            f,
+            "rules for update {} loop through {}",
+            join(&self.update),
+            join(&self.cycle)
+        )
+    }
+}
+
+/// The pages of `update` sorted so every rule between them holds.
+fn order(
+    page_by_before_set: &HashMap<u32, HashSet<u32>>,
+    update: &[u32],
+) -> Result<Vec<u32>, Cycle<u32>> {
+    // Only the rules between pages in this update matter, the full rule set is
+    // usually cyclic. The sort ignores rules to pages it wasn't given.
+    let rules = update.iter().flat_map(|before| {
+        page_by_before_set
+            .get(before)
+            .into_iter()
+            .flatten()
+            .map(move |after| (*before, *after))
+    });
+
+    topological_sort(update, rules)
+}
+
+/// The `(before, after)` rules broken by `update`, in the order the offending pages appear.
+fn violations(page_by_before_set: &HashMap<u32, HashSet<u32>>, update: &[u32]) -> Vec<(u32, u32)> {
+    let mut violations = Vec::new();
+    for (i, after) in update.iter().enumerate() {
+        for before in &update[i + 1..] {
+            if page_by_before_set
+                .get(before)
+                .is_some_and(|s| s.contains(after))
+            {
+                violations.push((*before, *after));
             }
         }
     }
 
-    true
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,61,53,29,13
+75,29,13
+75,97,47,61,53
+61,13,29
+97,13,75,29,47";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 143);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), Ok(123));
+    }
+
+    #[test]
+    fn test_violations() {
+        let (rules, _) = parse(EXAMPLE);
+        assert_eq!(violations(&rules, &[75, 97, 47, 61, 53]), vec![(97, 75)]);
+        assert_eq!(violations(&rules, &[61, 13, 29]), vec![(29, 13)]);
+        assert!(violations(&rules, &[75, 29, 13]).is_empty());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let (mut rules, _) = parse(EXAMPLE);
+        rules.entry(13).or_default().insert(97);
+
+        let Err(Cycle(cycle)) = order(&rules, &[97, 61, 13]) else {
+            panic!("expected a cycle");
+        };
+        // 97|13 is already a rule, so that's the loop that gets found.
+        assert_eq!(cycle.len(), 2);
+        assert!(cycle.contains(&97) && cycle.contains(&13));
+        assert_eq!(order(&rules, &[61, 13]), Ok(vec![61, 13]));
+
+        let report = part2(&(rules, vec![vec![13, 61, 97]])).unwrap_err();
+        assert_eq!(report.update, vec![13, 61, 97]);
+        assert_eq!(
+            report.to_string(),
+            "rules for update 13,61,97 loop through 97,61,13"
+        );
+    }
 }
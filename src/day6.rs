@@ -41,112 +41,382 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1((map, start_pos): &Input) -> u32 {
-    visited(map, *start_pos, Direction::Up).len() as u32
+    let trace = patrol(&JumpTable::new(map), *start_pos, Direction::Up);
+    assert_eq!(trace.end, PatrolEnd::LeftMap, "guard never leaves the map");
+    trace.visited().len() as u32
 }
 
 pub fn part2((map, start_pos): &Input) -> u32 {
-    let mut map = map.clone();
-
-    let visited = visited_order(&map, *start_pos, Direction::Up);
-    visited
-        .iter()
-        .map(|(pos, dir)| {
-            map.set(*pos, MapCell::Obstruction);
-            let has_loop = contains_loop(&map, pos.moved_in(dir.reversed()), *dir);
-            map.set(*pos, MapCell::Empty);
-
-            if has_loop {
-                1
-            } else {
-                0
-            }
-        })
-        .sum()
+    let jumps = JumpTable::new(map);
+    let trace = patrol(&jumps, *start_pos, Direction::Up);
+
+    // Only cells on the original route can change it. Putting the obstruction
+    // on a cell the first time the guard reaches it means everything up to there
+    // is unchanged, so each check can start from just before it.
+    let candidates = trace
+        .first_visits()
+        .into_iter()
+        .filter(|(pos, _)| pos != start_pos)
+        .collect::<Vec<_>>();
+
+    let n_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_len = candidates.len().div_ceil(n_threads).max(1);
+
+    std::thread::scope(|scope| {
+        let handles = candidates
+            .chunks(chunk_len)
+            .map(|chunk| {
+                let mut jumps = jumps.clone();
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter(|(pos, dir)| {
+                            jumps.add(*pos);
+                            let has_loop = loops(&jumps, pos.moved_in(dir.reversed()), *dir);
+                            jumps.remove(*pos);
+                            has_loop
+                        })
+                        .count() as u32
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    })
 }
 
-fn visited(map: &Grid<MapCell>, start_pos: Position, start_dir: Direction) -> HashSet<Position> {
-    let mut visited = HashSet::new();
+/// Obstructions sorted along each row and column,
+/// so the guard can jump straight to the next one instead of stepping cell by cell.
+#[derive(Clone, Debug)]
+pub struct JumpTable {
+    n_rows: i32,
+    n_cols: i32,
+    /// Obstruction columns in each row.
+    by_row: Vec<Vec<i32>>,
+    /// Obstruction rows in each column.
+    by_col: Vec<Vec<i32>>,
+}
 
-    let mut pos = start_pos.clone();
-    visited.insert(pos);
+impl JumpTable {
+    pub fn new(map: &Grid<MapCell>) -> Self {
+        let mut jumps = JumpTable {
+            n_rows: map.n_rows(),
+            n_cols: map.n_cols(),
+            by_row: vec![Vec::new(); map.n_rows() as usize],
+            by_col: vec![Vec::new(); map.n_cols() as usize],
+        };
+        // Row-major order keeps both lists sorted as they're built.
+        for (pos, cell) in map.iter() {
+            if cell == MapCell::Obstruction {
+                jumps.by_row[pos.row as usize].push(pos.col);
+                jumps.by_col[pos.col as usize].push(pos.row);
+            }
+        }
+        jumps
+    }
 
-    let mut dir = start_dir.clone();
-    loop {
-        let new_pos = pos.moved_in(dir);
-        match map.get(new_pos) {
-            None => break,
-            Some(MapCell::Empty) => {
-                visited.insert(new_pos);
-                pos = new_pos;
+    /// Only touches the obstruction's own row and column.
+    pub fn add(&mut self, pos: Position) {
+        let row = &mut self.by_row[pos.row as usize];
+        if let Err(i) = row.binary_search(&pos.col) {
+            row.insert(i, pos.col);
+        }
+        let col = &mut self.by_col[pos.col as usize];
+        if let Err(i) = col.binary_search(&pos.row) {
+            col.insert(i, pos.row);
+        }
+    }
+
+    pub fn remove(&mut self, pos: Position) {
+        let row = &mut self.by_row[pos.row as usize];
+        if let Ok(i) = row.binary_search(&pos.col) {
+            row.remove(i);
+        }
+        let col = &mut self.by_col[pos.col as usize];
+        if let Ok(i) = col.binary_search(&pos.row) {
+            col.remove(i);
+        }
+    }
+
+    /// Where walking from `pos` in `dir` stops, and whether that's because of an obstruction
+    /// (rather than reaching the edge of the map).
+    pub fn next_stop(&self, pos: Position, dir: Direction) -> (Position, bool) {
+        let (line, x, max) = match dir {
+            Direction::Up | Direction::Down => {
+                (&self.by_col[pos.col as usize], pos.row, self.n_rows - 1)
             }
-            Some(MapCell::Obstruction) => {
-                dir = dir.rotated_clockwise();
+            Direction::Left | Direction::Right => {
+                (&self.by_row[pos.row as usize], pos.col, self.n_cols - 1)
             }
+        };
+
+        let (stop, blocked) = match dir {
+            Direction::Up | Direction::Left => {
+                let i = line.partition_point(|o| *o < x);
+                match i.checked_sub(1) {
+                    Some(i) => (line[i] + 1, true),
+                    None => (0, false),
+                }
+            }
+            Direction::Down | Direction::Right => {
+                let i = line.partition_point(|o| *o <= x);
+                match line.get(i) {
+                    Some(o) => (o - 1, true),
+                    None => (max, false),
+                }
+            }
+        };
+
+        match dir {
+            Direction::Up | Direction::Down => (Position::new(stop, pos.col), blocked),
+            Direction::Left | Direction::Right => (Position::new(pos.row, stop), blocked),
         }
     }
+}
 
-    visited
+/// A straight run of the guard's route, both ends inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Segment {
+    pub start: Position,
+    pub end: Position,
+    pub dir: Direction,
 }
 
-// For part 2, track the first time we visit each position.
-// By starting from the first visited pos and trying them in visited order,
-// we can simulate from just before hitting the new obstacle
-// instead of all the way from the starting position.
-fn visited_order(
-    map: &Grid<MapCell>,
-    start_pos: Position,
-    start_dir: Direction,
-) -> Vec<(Position, Direction)> {
-    let mut visited_order = Vec::new();
-    let mut visited = HashSet::new();
+impl Segment {
+    pub fn cells(&self) -> impl Iterator<Item = Position> + '_ {
+        let len = self.start.distance_to(&self.end) as i32;
+        (0..=len).map(|i| self.start + self.dir * i)
+    }
+}
 
-    let mut pos = start_pos.clone();
-    visited.insert(pos);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatrolEnd {
+    LeftMap,
+    Looped,
+}
 
-    let mut dir = start_dir.clone();
-    loop {
-        let new_pos = pos.moved_in(dir);
-        match map.get(new_pos) {
-            None => break,
-            Some(MapCell::Empty) => {
-                if !visited.contains(&new_pos) {
-                    visited_order.push((new_pos, dir));
-                    visited.insert(new_pos);
-                }
+/// The guard's route as the segments between turns.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pub segments: Vec<Segment>,
+    pub end: PatrolEnd,
+}
 
-                pos = new_pos;
+impl Trace {
+    pub fn visited(&self) -> HashSet<Position> {
+        self.segments.iter().flat_map(|s| s.cells()).collect()
+    }
+
+    /// Every visited cell with the direction the guard was facing the first time it got there,
+    /// in the order they're reached.
+    pub fn first_visits(&self) -> Vec<(Position, Direction)> {
+        let mut visited = HashSet::new();
+        self.segments
+            .iter()
+            .flat_map(|s| s.cells().map(|p| (p, s.dir)).collect::<Vec<_>>())
+            .filter(|(p, _)| visited.insert(*p))
+            .collect()
+    }
+
+    /// The map with the route drawn on it, AoC style:
+    /// `|` and `-` for the direction of travel, `+` where the route turns or crosses itself.
+    #[allow(dead_code)]
+    pub fn render(&self, map: &Grid<MapCell>) -> String {
+        let mut canvas = Grid::new(map.n_rows() as usize, map.n_cols() as usize, '.');
+        for (pos, cell) in map.iter() {
+            if cell == MapCell::Obstruction {
+                canvas.set(pos, '#');
             }
-            Some(MapCell::Obstruction) => {
-                dir = dir.rotated_clockwise();
+        }
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            let line = match segment.dir {
+                Direction::Up | Direction::Down => '|',
+                Direction::Left | Direction::Right => '-',
+            };
+            for pos in segment.cells() {
+                let c = match canvas.get(pos) {
+                    Some('.') => line,
+                    Some(c) if *c == line => line,
+                    _ => '+',
+                };
+                canvas.set(pos, c);
+            }
+            if i + 1 < self.segments.len() {
+                canvas.set(segment.end, '+');
             }
         }
+
+        if let Some(first) = self.segments.first() {
+            canvas.set(first.start, first.dir.to_arrow());
+        }
+
+        canvas.render(|_, c| *c)
     }
+}
 
-    visited_order
+/// Walks the guard until it leaves the map or starts repeating itself.
+pub fn patrol(jumps: &JumpTable, start_pos: Position, start_dir: Direction) -> Trace {
+    let mut segments = Vec::new();
+    let end = walk(jumps, start_pos, start_dir, |s| segments.push(s));
+    Trace { segments, end }
 }
 
-fn contains_loop(map: &Grid<MapCell>, start_pos: Position, start_dir: Direction) -> bool {
-    let mut visited = HashSet::new();
+/// Same as `patrol` but without keeping the trace around.
+fn loops(jumps: &JumpTable, start_pos: Position, start_dir: Direction) -> bool {
+    walk(jumps, start_pos, start_dir, |_| {}) == PatrolEnd::Looped
+}
 
-    let mut pos = start_pos.clone();
-    let mut dir = start_dir.clone();
-    visited.insert((pos, dir));
+fn walk(
+    jumps: &JumpTable,
+    start_pos: Position,
+    start_dir: Direction,
+    mut on_segment: impl FnMut(Segment),
+) -> PatrolEnd {
+    // The route only repeats if a turn does, so that's all that needs remembering.
+    let mut turns = HashSet::new();
 
+    let (mut pos, mut dir) = (start_pos, start_dir);
     loop {
-        let new_pos = pos.moved_in(dir);
-        match map.get(new_pos) {
-            None => break,
-            Some(MapCell::Empty) => {
-                if !visited.insert((new_pos, dir)) {
-                    return true;
+        let (end, blocked) = jumps.next_stop(pos, dir);
+        on_segment(Segment {
+            start: pos,
+            end,
+            dir,
+        });
+
+        if !blocked {
+            return PatrolEnd::LeftMap;
+        }
+        if !turns.insert((end, dir)) {
+            return PatrolEnd::Looped;
+        }
+
+        (pos, dir) = (end, dir.rotated_clockwise());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::Rng;
+
+    const EXAMPLE: &str = "....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 41);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 6);
+    }
+
+    #[test]
+    fn test_render() {
+        let map: Grid<MapCell> = "..#.\n...#\n....\n..^.".into();
+        let trace = patrol(&JumpTable::new(&map), Position::new(3, 2), Direction::Up);
+
+        assert_eq!(trace.end, PatrolEnd::LeftMap);
+        assert_eq!(trace.render(&map), "..#.\n..+#\n..|.\n..^.\n");
+    }
+
+    #[test]
+    fn test_jump_table_updates() {
+        let (map, start_pos) = parse(EXAMPLE);
+        let mut jumps = JumpTable::new(&map);
+        let before = jumps.next_stop(start_pos, Direction::Up);
+
+        jumps.add(Position::new(3, 4));
+        assert_eq!(
+            jumps.next_stop(start_pos, Direction::Up),
+            (Position::new(4, 4), true)
+        );
+
+        jumps.remove(Position::new(3, 4));
+        assert_eq!(jumps.next_stop(start_pos, Direction::Up), before);
+        assert_eq!(before, (Position::new(1, 4), true));
+    }
+
+    // The original cell by cell simulation, to check the jump tables against.
+    fn simulate_part2(map: &Grid<MapCell>, start_pos: Position) -> u32 {
+        fn contains_loop(map: &Grid<MapCell>, mut pos: Position, mut dir: Direction) -> bool {
+            let mut visited = HashSet::new();
+            visited.insert((pos, dir));
+            loop {
+                let new_pos = pos.moved_in(dir);
+                match map.get(new_pos) {
+                    None => return false,
+                    Some(MapCell::Empty) => {
+                        if !visited.insert((new_pos, dir)) {
+                            return true;
+                        }
+                        pos = new_pos;
+                    }
+                    Some(MapCell::Obstruction) => dir = dir.rotated_clockwise(),
                 }
-                pos = new_pos;
             }
-            Some(MapCell::Obstruction) => {
-                dir = dir.rotated_clockwise();
+        }
+
+        let mut map = map.clone();
+        let mut count = 0;
+        for row in 0..map.n_rows() {
+            for col in 0..map.n_cols() {
+                let pos = Position::new(row, col);
+                if pos == start_pos || map.is(pos, MapCell::Obstruction) {
+                    continue;
+                }
+                map.set(pos, MapCell::Obstruction);
+                if contains_loop(&map, start_pos, Direction::Up) {
+                    count += 1;
+                }
+                map.set(pos, MapCell::Empty);
             }
         }
+        count
     }
 
-    false
+    #[test]
+    fn test_matches_simulation() {
+        let mut rng = Rng::new(3);
+
+        let mut compared = 0;
+        for _ in 0..30 {
+            let mut input = String::new();
+            for row in 0..16 {
+                for col in 0..16 {
+                    input.push(match (row, col) {
+                        (12, 8) => '^',
+                        _ if rng.below(8) == 0 => '#',
+                        _ => '.',
+                    });
+                }
+                input.push('\n');
+            }
+
+            let (map, start_pos) = parse(&input);
+            // AoC inputs always let the guard out.
+            if patrol(&JumpTable::new(&map), start_pos, Direction::Up).end == PatrolEnd::Looped {
+                continue;
+            }
+            assert_eq!(
+                part2(&(map.clone(), start_pos)),
+                simulate_part2(&map, start_pos),
+                "\n{}",
+                input
+            );
+            compared += 1;
+        }
+        assert!(compared >= 10, "only {} maps let the guard out", compared);
+    }
 }
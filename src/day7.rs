@@ -11,41 +11,392 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u64 {
-    input.iter().filter(|(goal, operands)| is_equation_possibly_true(false, *goal, operands)).map(|(goal, _)| goal).sum()
+    total_calibration(&[&Add, &Mul], input)
 }
 
 pub fn part2(input: &Input) -> u64 {
-    input.iter().filter(|(goal, operands)| is_equation_possibly_true(true, *goal, operands)).map(|(goal, _)| goal).sum()
+    total_calibration(&[&Add, &Mul, &Concat::DECIMAL], input)
 }
 
-fn is_equation_possibly_true(with_concat: bool, goal: u64, operands: &Vec<u64>) -> bool {
-    let mut goals = Vec::new();
-    goals.push(goal);
+fn total_calibration(operators: &[&dyn Operator], input: &Input) -> u64 {
+    input
+        .iter()
+        .filter(|(goal, operands)| solve(operators, *goal, operands).is_some())
+        .map(|(goal, _)| goal)
+        .sum()
+}
 
-    for operand in operands.iter().rev() {
-        let mut new_goals = Vec::new();
-        for goal in goals.iter() {
-            // +
-            if goal >= operand {
-                new_goals.push(goal - operand);
-            }
+/// A binary operator that can go in the gaps of an equation, evaluated left to right.
+pub trait Operator {
+    fn symbol(&self) -> &'static str;
+
+    /// `lhs op rhs`, or `None` if it doesn't fit in a `u64`.
+    fn apply(&self, lhs: u64, rhs: u64) -> Option<u64>;
+
+    /// The `lhs` for which `lhs op rhs == result`, if there's exactly one.
+    /// Solving works backwards from the goal with this, which prunes far more than going forwards.
+    fn inverse(&self, result: u64, rhs: u64) -> Option<u64>;
+
+    /// Whether `lhs op rhs == result` for every `lhs`, in which case `inverse` has nothing to say.
+    fn absorbs(&self, _result: u64, _rhs: u64) -> bool {
+        false
+    }
+}
 
-            // *
-            if goal % operand == 0 {
-                new_goals.push(goal / operand);
+pub struct Add;
+
+impl Operator for Add {
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn apply(&self, lhs: u64, rhs: u64) -> Option<u64> {
+        lhs.checked_add(rhs)
+    }
+
+    fn inverse(&self, result: u64, rhs: u64) -> Option<u64> {
+        result.checked_sub(rhs)
+    }
+}
+
+// Not in the puzzle, but the solver copes with it.
+#[allow(dead_code)]
+pub struct Sub;
+
+impl Operator for Sub {
+    fn symbol(&self) -> &'static str {
+        "-"
+    }
+
+    fn apply(&self, lhs: u64, rhs: u64) -> Option<u64> {
+        lhs.checked_sub(rhs)
+    }
+
+    fn inverse(&self, result: u64, rhs: u64) -> Option<u64> {
+        result.checked_add(rhs)
+    }
+}
+
+pub struct Mul;
+
+impl Operator for Mul {
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn apply(&self, lhs: u64, rhs: u64) -> Option<u64> {
+        lhs.checked_mul(rhs)
+    }
+
+    // Anything times 0 is 0, so there's no single answer for that, see `absorbs`.
+    fn inverse(&self, result: u64, rhs: u64) -> Option<u64> {
+        (rhs != 0 && result.is_multiple_of(rhs)).then(|| result / rhs)
+    }
+
+    fn absorbs(&self, result: u64, rhs: u64) -> bool {
+        rhs == 0 && result == 0
+    }
+}
+
+/// Appends the digits of `rhs` to `lhs`, written in `base`.
+pub struct Concat {
+    pub base: u64,
+}
+
+impl Concat {
+    pub const DECIMAL: Concat = Concat { base: 10 };
+
+    /// `base` to the power of the number of digits in `n`.
+    fn shift(&self, n: u64) -> Option<u64> {
+        self.base
+            .checked_pow(n.checked_ilog(self.base).unwrap_or(0) + 1)
+    }
+}
+
+impl Operator for Concat {
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+
+    fn apply(&self, lhs: u64, rhs: u64) -> Option<u64> {
+        lhs.checked_mul(self.shift(rhs)?)?.checked_add(rhs)
+    }
+
+    fn inverse(&self, result: u64, rhs: u64) -> Option<u64> {
+        // Too many digits to fit means it can't be a suffix of anything.
+        let shift = self.shift(rhs)?;
+        (result % shift == rhs).then(|| result / shift)
+    }
+}
+
+/// A choice of operators that makes an equation true.
+pub struct Witness<'a> {
+    pub goal: u64,
+    pub operands: &'a [u64],
+    pub operators: Vec<&'a dyn Operator>,
+}
+
+impl Witness<'_> {
+    /// Evaluates left to right, `None` on overflow.
+    pub fn evaluate(&self) -> Option<u64> {
+        self.operators
+            .iter()
+            .zip(&self.operands[1..])
+            .try_fold(self.operands[0], |acc, (op, n)| op.apply(acc, *n))
+    }
+}
+
+impl std::fmt::Display for Witness<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.operands[0])?;
+        for (op, n) in self.operators.iter().zip(&self.operands[1..]) {
+            write!(f, " {} {}", op.symbol(), n)?;
+        }
+        write!(f, " = {}", self.goal)
+    }
+}
+
+/// The first operator assignment found that makes `operands` evaluate to `goal`.
+pub fn solve<'a>(
+    operators: &[&'a dyn Operator],
+    goal: u64,
+    operands: &'a [u64],
+) -> Option<Witness<'a>> {
+    fn search<'a>(
+        operators: &[&'a dyn Operator],
+        goal: u64,
+        operands: &[u64],
+        chosen: &mut Vec<&'a dyn Operator>,
+    ) -> bool {
+        let Some((last, rest)) = operands.split_last() else {
+            return false;
+        };
+        if rest.is_empty() {
+            return goal == *last;
+        }
+
+        // Every step is checked going forwards too, since overflow is exactly what
+        // inverses don't catch, and a witness that overflows shouldn't stop the search.
+        for op in operators {
+            if op.absorbs(goal, *last) {
+                // Whatever the rest comes to works, as long as it comes to something.
+                chosen.push(*op);
+                let mark = chosen.len();
+                let finish = |acc| op.apply(acc, *last) == Some(goal);
+                if any_value(operators, rest[0], &rest[1..], chosen, &finish) {
+                    // Found left to right, but `chosen` is right to left.
+                    chosen[mark..].reverse();
+                    return true;
+                }
+                chosen.pop();
+            } else if let Some(lhs) = op
+                .inverse(goal, *last)
+                .filter(|lhs| op.apply(*lhs, *last) == Some(goal))
+            {
+                chosen.push(*op);
+                if search(operators, lhs, rest, chosen) {
+                    return true;
+                }
+                chosen.pop();
             }
+        }
+        false
+    }
+
+    fn any_value<'a>(
+        operators: &[&'a dyn Operator],
+        acc: u64,
+        operands: &[u64],
+        chosen: &mut Vec<&'a dyn Operator>,
+        finish: &dyn Fn(u64) -> bool,
+    ) -> bool {
+        let Some((next, rest)) = operands.split_first() else {
+            return finish(acc);
+        };
 
-            // ||
-            if with_concat {
-                let multiplier = 10u64.pow(operand.ilog10() + 1);
-                if (goal / multiplier) * multiplier + operand == *goal {
-                    new_goals.push(goal / (10u64.pow(operand.ilog10() + 1)));
+        for op in operators {
+            if let Some(acc) = op.apply(acc, *next) {
+                chosen.push(*op);
+                if any_value(operators, acc, rest, chosen, finish) {
+                    return true;
                 }
+                chosen.pop();
+            }
+        }
+        false
+    }
+
+    let mut chosen = Vec::new();
+    if !search(operators, goal, operands, &mut chosen) {
+        return None;
+    }
+    // Picked from the right hand end first.
+    chosen.reverse();
+
+    let witness = Witness {
+        goal,
+        operands,
+        operators: chosen,
+    };
+    debug_assert_eq!(witness.evaluate(), Some(goal));
+    Some(witness)
+}
+
+/// How many operator assignments make `operands` evaluate to `goal`.
+#[allow(dead_code)]
+pub fn count_solutions(operators: &[&dyn Operator], goal: u64, operands: &[u64]) -> u64 {
+    let Some((last, rest)) = operands.split_last() else {
+        return 0;
+    };
+    if rest.is_empty() {
+        return (goal == *last) as u64;
+    }
+
+    operators
+        .iter()
+        .map(|op| {
+            if op.absorbs(goal, *last) {
+                count_evaluations(operators, rest[0], &rest[1..])
+            } else {
+                op.inverse(goal, *last)
+                    .filter(|lhs| op.apply(*lhs, *last) == Some(goal))
+                    .map_or(0, |lhs| count_solutions(operators, lhs, rest))
             }
+        })
+        .sum()
+}
+
+/// How many operator assignments evaluate to anything at all, starting from `acc`.
+fn count_evaluations(operators: &[&dyn Operator], acc: u64, operands: &[u64]) -> u64 {
+    let Some((next, rest)) = operands.split_first() else {
+        return 1;
+    };
+
+    operators
+        .iter()
+        .filter_map(|op| op.apply(acc, *next))
+        .map(|acc| count_evaluations(operators, acc, rest))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "190: 10 19
+3267: 81 40 27
+83: 17 5
+156: 15 6
+7290: 6 8 6 15
+161011: 16 10 13
+192: 17 8 14
+21037: 9 7 18 13
+292: 11 6 16 20";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 3749);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 11387);
+    }
+
+    #[test]
+    fn test_witness() {
+        let ops: [&dyn Operator; 3] = [&Add, &Mul, &Concat::DECIMAL];
+        let operands = [81, 40, 27];
+
+        let witness = solve(&ops, 3267, &operands).unwrap();
+        assert_eq!(witness.to_string(), "81 * 40 + 27 = 3267");
+        assert_eq!(witness.evaluate(), Some(3267));
+        assert_eq!(count_solutions(&ops, 3267, &operands), 2);
+
+        let witness = solve(&ops, 7290, &[6, 8, 6, 15]).unwrap();
+        assert_eq!(witness.to_string(), "6 * 8 || 6 * 15 = 7290");
+
+        assert!(solve(&ops, 83, &[17, 5]).is_none());
+        assert_eq!(count_solutions(&ops, 83, &[17, 5]), 0);
+    }
+
+    #[test]
+    fn test_other_operators() {
+        let ops: [&dyn Operator; 2] = [&Sub, &Concat { base: 2 }];
+
+        // 0b101 || 0b11 = 0b10111
+        assert_eq!(Concat { base: 2 }.apply(5, 3), Some(23));
+        assert_eq!(Concat { base: 2 }.inverse(23, 3), Some(5));
+        assert_eq!(
+            solve(&ops, 22, &[5, 3, 1]).unwrap().to_string(),
+            "5 || 3 - 1 = 22"
+        );
+
+        assert_eq!(Concat::DECIMAL.apply(12, 0), Some(120));
+    }
+
+    #[test]
+    fn test_zero_operand() {
+        let ops: [&dyn Operator; 2] = [&Add, &Mul];
+        assert_eq!(solve(&ops, 0, &[5, 0]).unwrap().to_string(), "5 * 0 = 0");
+        assert_eq!(count_solutions(&ops, 0, &[5, 0]), 1);
+        assert!(solve(&ops, 1, &[5, 0]).is_none());
+
+        // Anything before the 0 works, both ways round.
+        assert_eq!(count_solutions(&ops, 0, &[5, 3, 0]), 2);
+        assert_eq!(
+            solve(&ops, 4, &[2, 3, 0, 4]).unwrap().to_string(),
+            "2 + 3 * 0 + 4 = 4"
+        );
+        assert_eq!(count_solutions(&ops, 4, &[2, 3, 0, 4]), 2);
+
+        // As long as it doesn't underflow.
+        let ops: [&dyn Operator; 2] = [&Sub, &Mul];
+        assert_eq!(count_solutions(&ops, 0, &[3, 5, 0]), 1);
+        assert_eq!(
+            solve(&ops, 0, &[3, 5, 0]).unwrap().to_string(),
+            "3 * 5 * 0 = 0"
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(Mul.apply(u64::MAX, 2), None);
+        assert_eq!(Concat::DECIMAL.apply(u64::MAX / 10, 12), None);
+        assert_eq!(Concat::DECIMAL.inverse(5, u64::MAX), None);
+        assert_eq!(Sub.inverse(u64::MAX, 1), None);
+
+        let big = u64::MAX / 2 + 1;
+        assert!(solve(&[&Add], 0, &[big, big]).is_none());
+    }
+
+    /// `+` whose inverse wraps, so it offers a `lhs` that overflows going forwards.
+    struct WrappingInverseAdd;
+
+    impl Operator for WrappingInverseAdd {
+        fn symbol(&self) -> &'static str {
+            "+"
+        }
+
+        fn apply(&self, lhs: u64, rhs: u64) -> Option<u64> {
+            lhs.checked_add(rhs)
         }
 
-        goals = new_goals;
+        fn inverse(&self, result: u64, rhs: u64) -> Option<u64> {
+            Some(result.wrapping_sub(rhs))
+        }
     }
 
-    goals.contains(&0)
+    #[test]
+    fn test_backtrack_past_overflow() {
+        // Going backwards, `+ 2` then `+ y` reach the first operand, but that overflows
+        // going forwards. `- y - 2` is the real solution.
+        let ops: [&dyn Operator; 2] = [&WrappingInverseAdd, &Sub];
+        let y = (u64::MAX - 3) / 2;
+        let operands = [y + 3, y, 2];
+
+        let witness = solve(&ops, 1, &operands).unwrap();
+        assert_eq!(witness.to_string(), format!("{} - {} - 2 = 1", y + 3, y));
+        assert_eq!(count_solutions(&ops, 1, &operands), 1);
+    }
 }
@@ -18,50 +18,140 @@ pub fn parse(input: &str) -> Input {
 }
 
 pub fn part1((grid, antennas): &Input) -> u32 {
-    let mut antinodes = HashSet::new();
- 
-    for (_, antenna_locations) in antennas {
-        for (i, a) in antenna_locations.iter().enumerate() {
-            for b in &antenna_locations[i+1..] {
-                let diff = b - a;
-
-                let x = a - diff;
-                if grid.get(x).is_some() {
-                    antinodes.insert(x);
-                }
-                let y = b + diff;
-                if grid.get(y).is_some() {
-                    antinodes.insert(y);
+    count(&antinodes(grid, antennas, &Harmonics::part1()))
+}
+
+pub fn part2((grid, antennas): &Input) -> u32 {
+    count(&antinodes(grid, antennas, &Harmonics::All))
+}
+
+/// Prints the map with the antinodes on it, for `day8-antinodes`.
+pub fn print_antinodes((grid, antennas): &Input, harmonics: &Harmonics) {
+    let antinodes = antinodes(grid, antennas, harmonics);
+    print!("{}", render(grid, &antinodes));
+    println!("{} antinodes", count(&antinodes));
+}
+
+fn count(antinodes: &HashMap<char, HashSet<Position>>) -> u32 {
+    antinodes.values().flatten().collect::<HashSet<_>>().len() as u32
+}
+
+/// Which points on the line through a pair of antennas `a` and `b` are antinodes.
+pub enum Harmonics {
+    /// `a + k * (b - a)` for each `k`, so `a` is 0 and `b` is 1.
+    Multiples(Vec<i32>),
+    /// Every lattice point on the line, including any between the two antennas.
+    All,
+}
+
+impl Harmonics {
+    /// One antinode beyond each antenna of the pair.
+    pub fn part1() -> Self {
+        Harmonics::Multiples(vec![-1, 2])
+    }
+}
+
+/// Antinodes within the grid for each frequency.
+pub fn antinodes(
+    grid: &Grid<char>,
+    antennas: &HashMap<char, Vec<Position>>,
+    harmonics: &Harmonics,
+) -> HashMap<char, HashSet<Position>> {
+    let in_grid = |p: &Position| grid.get(*p).is_some();
+
+    antennas
+        .iter()
+        .map(|(frequency, locations)| {
+            let mut antinodes = HashSet::new();
+
+            for (i, a) in locations.iter().enumerate() {
+                for b in &locations[i + 1..] {
+                    match harmonics {
+                        Harmonics::Multiples(ks) => {
+                            antinodes.extend(ks.iter().map(|k| a + (b - a) * *k).filter(in_grid))
+                        }
+                        // Steps by the gcd-reduced difference, so nothing collinear is skipped.
+                        Harmonics::All => antinodes.extend(a.line_within(*b, grid.bounds())),
+                    }
                 }
             }
+
+            (*frequency, antinodes)
+        })
+        .collect()
+}
+
+/// The map with `#` on every antinode that isn't already showing an antenna.
+pub fn render(grid: &Grid<char>, antinodes: &HashMap<char, HashSet<Position>>) -> String {
+    let mut overlay = grid.clone();
+    for p in antinodes.values().flatten() {
+        if grid.is(*p, '.') {
+            overlay.set(*p, '#');
         }
     }
 
-    antinodes.len() as u32
+    overlay.render(|_, c| *c)
 }
 
-pub fn part2((grid, antennas): &Input) -> u32 {
-    let mut antinodes = HashSet::new();
- 
-    for (_, antenna_locations) in antennas {
-        for (i, a) in antenna_locations.iter().enumerate() {
-            for b in &antenna_locations[i+1..] {
-                let diff = b - a;
-
-                let mut x = a.clone();
-                while grid.get(x).is_some() {
-                    antinodes.insert(x);
-                    x -= diff;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                let mut y = b.clone();
-                while grid.get(y).is_some() {
-                    antinodes.insert(y);
-                    y += diff;
-                }
-            }
-        }
+    const EXAMPLE: &str = "............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 14);
     }
 
-    antinodes.len() as u32
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 34);
+    }
+
+    #[test]
+    fn test_render() {
+        let (grid, antennas) = parse(EXAMPLE);
+        let antinodes = antinodes(&grid, &antennas, &Harmonics::Multiples(vec![-1, 2]));
+        assert_eq!(antinodes[&'A'].len(), 5);
+
+        let expected = "......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+";
+        assert_eq!(render(&grid, &antinodes), expected);
+    }
+
+    #[test]
+    fn test_harmonics() {
+        // Antennas 2 apart diagonally have a lattice point between them.
+        let (grid, antennas) = parse("a....\n.....\n..a..\n.....\n.....");
+
+        let all = antinodes(&grid, &antennas, &Harmonics::All);
+        assert_eq!(all[&'a'].len(), 5);
+        assert!(all[&'a'].contains(&Position::new(1, 1)));
+
+        let whole = antinodes(&grid, &antennas, &Harmonics::Multiples((-5..=5).collect()));
+        assert_eq!(whole[&'a'].len(), 3);
+    }
 }
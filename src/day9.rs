@@ -1,118 +1,639 @@
-#[derive(Clone, Debug)]
-pub enum DiskBlock {
-    File(u64),
-    FreeSpace,
+use std::{collections::HashMap, ops::Range};
+
+use crate::util::ranges::RangeSet;
+
+/// A contiguous run of blocks belonging to one file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Extent {
+    pub id: u64,
+    pub pos: usize,
+    pub len: usize,
+}
+
+/// `len` blocks of file `id` moving from `from` to `to` during compaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub id: u64,
+    pub from: usize,
+    pub to: usize,
+    pub len: usize,
 }
 
-type Input = Vec<DiskBlock>;
+/// Run-length disk layout: the file extents in order, with free space being whatever's between them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disk {
+    extents: Vec<Extent>,
+    size: usize,
+}
+
+type Input = Disk;
 
 pub fn parse(input: &str) -> Input {
-    input
-        .chars()
-        .enumerate()
-        .flat_map(|(i, c)| {
-            let size = c.to_digit(10).unwrap();
-            let block = if i % 2 == 0 {
-                DiskBlock::File(i as u64 / 2)
-            } else {
-                DiskBlock::FreeSpace
+    Disk::from_dense(input.trim_end()).unwrap()
+}
+
+pub fn part1(input: &Input) -> u64 {
+    input.compact_blocks().checksum()
+}
+
+pub fn part2(input: &Input) -> u64 {
+    input.compact_files().checksum()
+}
+
+impl Disk {
+    /// Alternating file and free space lengths as digits, starting with file 0.
+    pub fn from_dense(dense: &str) -> Option<Disk> {
+        let mut extents = Vec::new();
+        let mut pos = 0;
+        for (i, c) in dense.chars().enumerate() {
+            let len = c.to_digit(10)? as usize;
+            if i % 2 == 0 {
+                extents.push(Extent {
+                    id: i as u64 / 2,
+                    pos,
+                    len,
+                });
+            }
+            pos += len;
+        }
+
+        Some(Disk { extents, size: pos })
+    }
+
+    /// The free runs, in order.
+    /// Zero-length files don't take up any space, so they don't split the run they sit in.
+    pub fn gaps(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let files = || self.extents.iter().filter(|e| e.len > 0);
+        let ends = std::iter::once(0).chain(files().map(|e| e.pos + e.len));
+        let starts = files().map(|e| e.pos).chain([self.size]);
+        ends.zip(starts)
+            .map(|(a, b)| a..b)
+            .filter(|r| !r.is_empty())
+    }
+
+    pub fn checksum(&self) -> u64 {
+        self.extents
+            .iter()
+            .map(|e| {
+                let (pos, len) = (e.pos as u64, e.len as u64);
+                // Sum of pos..pos + len.
+                e.id * (len * pos + len * len.saturating_sub(1) / 2)
+            })
+            .sum()
+    }
+
+    /// Moves blocks one at a time from the end of the disk into the leftmost free block,
+    /// until there are no gaps.
+    pub fn compact_blocks(&self) -> Disk {
+        self.compact_blocks_with(|_| {})
+    }
+
+    /// `compact_blocks`, reporting each run of blocks moved to the same gap as one move.
+    pub fn compact_blocks_with(&self, mut on_move: impl FnMut(Move)) -> Disk {
+        let mut files = self
+            .extents
+            .iter()
+            .filter(|e| e.len > 0)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut compacted: Vec<Extent> = Vec::with_capacity(files.len());
+
+        let mut push = |e: Extent| match compacted.last_mut() {
+            Some(last) if last.id == e.id && last.pos + last.len == e.pos => last.len += e.len,
+            _ => compacted.push(e),
+        };
+
+        let mut cursor = 0;
+        let mut i = 0;
+        while i < files.len() {
+            let next = files[i];
+            if next.pos == cursor {
+                push(next);
+                cursor += next.len;
+                i += 1;
+                continue;
+            }
+
+            // Fill the gap before `next` from the back, which may be `next` itself.
+            let last = files.last_mut().unwrap();
+            let len = (next.pos - cursor).min(last.len);
+            last.len -= len;
+            on_move(Move {
+                id: last.id,
+                from: last.pos + last.len,
+                to: cursor,
+                len,
+            });
+            push(Extent {
+                id: last.id,
+                pos: cursor,
+                len,
+            });
+            cursor += len;
+            if last.len == 0 {
+                files.pop();
+            }
+        }
+
+        Disk {
+            extents: compacted,
+            size: self.size,
+        }
+    }
+
+    /// Tries to move each extent once, right to left, into the leftmost gap it fits in whole.
+    ///
+    /// Gaps are kept in a `RangeSet`, so finding the leftmost fit is logarithmic
+    /// however long the gaps get, e.g. on a disk that's already been compacted.
+    pub fn compact_files(&self) -> Disk {
+        self.compact_files_with(|_| {})
+    }
+
+    /// `compact_files`, reporting every extent that moves.
+    pub fn compact_files_with(&self, mut on_move: impl FnMut(Move)) -> Disk {
+        let mut free = self
+            .gaps()
+            .map(|g| g.start as i64..g.end as i64)
+            .collect::<RangeSet>();
+
+        // No need to free up the space an extent moves out of,
+        // the extents still to move are all to the left of it and only move further left.
+        let mut extents = self.extents.clone();
+        for e in extents.iter_mut().rev().filter(|e| e.len > 0) {
+            let Some(gap) = free.first_fit(e.len as i64, e.pos as i64) else {
+                continue;
             };
+            let start = gap.start as usize;
 
-            vec![block; size as usize]
-        })
-        .collect()
+            free.remove(gap.start..gap.start + e.len as i64);
+            on_move(Move {
+                id: e.id,
+                from: e.pos,
+                to: start,
+                len: e.len,
+            });
+            e.pos = start;
+        }
+
+        extents.sort_by_key(|e| e.pos);
+        Disk {
+            extents,
+            size: self.size,
+        }
+    }
 }
 
-pub fn part1(input: &Input) -> u64 {
-    let mut compacted_blocks = Vec::with_capacity(input.len());
-
-    let mut i = 0;
-    let mut j = input.len() - 1;
-
-    'outer: while i <= j {
-        let block = &input[i];
-
-        match block {
-            DiskBlock::File(_) => compacted_blocks.push(block.clone()),
-            DiskBlock::FreeSpace => {
-                let mut new_block = &input[j];
-                while matches!(new_block, DiskBlock::FreeSpace) {
-                    j -= 1;
-                    if j <= i {
-                        break 'outer;
-                    }
+// For watching the compactions with `day9-replay`.
+impl Disk {
+    /// Total number of blocks, used or not.
+    pub fn size(&self) -> usize {
+        self.size
+    }
 
-                    new_block = &input[j];
-                }
+    /// The puzzle's notation, e.g. `00...111...2`.
+    /// Ids past 9 only show their last digit.
+    pub fn render(&self) -> String {
+        self.render_window(0..self.size)
+    }
 
-                compacted_blocks.push(new_block.clone());
-                j -= 1;
+    /// `render` for just the blocks in `window`, for disks too big to print whole.
+    pub fn render_window(&self, window: Range<usize>) -> String {
+        let window = window.start.min(self.size)..window.end.min(self.size);
+        let mut blocks = vec!['.'; window.len()];
+
+        let first = self
+            .extents
+            .partition_point(|e| e.pos + e.len <= window.start);
+        for e in self.extents[first..]
+            .iter()
+            .take_while(|e| e.pos < window.end)
+        {
+            let digit = char::from_digit((e.id % 10) as u32, 10).unwrap();
+            for pos in e.pos.max(window.start)..(e.pos + e.len).min(window.end) {
+                blocks[pos - window.start] = digit;
             }
         }
 
-        i += 1;
+        blocks.into_iter().collect()
     }
 
-    compacted_blocks
-        .into_iter()
-        .enumerate()
-        .map(|(i, b)| match b {
-            DiskBlock::FreeSpace => 0,
-            DiskBlock::File(id) => i as u64 * id,
-        })
-        .sum()
+    /// Redoes a move reported by one of the compactions.
+    pub fn apply(&mut self, mv: Move) {
+        // Cut the blocks out of the extent they're in...
+        let i = self.extents.partition_point(|e| e.pos + e.len <= mv.from);
+        let e = self.extents[i];
+        assert!(
+            e.id == mv.id && e.pos <= mv.from && mv.from + mv.len <= e.pos + e.len,
+            "{:?} doesn't match {:?}",
+            mv,
+            e
+        );
+        let pieces = [
+            Extent {
+                len: mv.from - e.pos,
+                ..e
+            },
+            Extent {
+                pos: mv.from + mv.len,
+                len: e.pos + e.len - (mv.from + mv.len),
+                ..e
+            },
+        ];
+        self.extents
+            .splice(i..=i, pieces.into_iter().filter(|p| p.len > 0));
+
+        // ...and put them down, joining up with the same file either side.
+        let mut j = self.extents.partition_point(|e| e.pos < mv.to);
+        self.extents.insert(
+            j,
+            Extent {
+                id: mv.id,
+                pos: mv.to,
+                len: mv.len,
+            },
+        );
+        if let Some(next) = self.extents.get(j + 1).copied() {
+            if next.id == mv.id && mv.to + mv.len == next.pos {
+                self.extents[j].len += next.len;
+                self.extents.remove(j + 1);
+            }
+        }
+        if j > 0 {
+            let prev = self.extents[j - 1];
+            if prev.id == mv.id && prev.pos + prev.len == mv.to {
+                self.extents[j - 1].len += self.extents[j].len;
+                self.extents.remove(j);
+                j -= 1;
+            }
+        }
+        debug_assert!(self.extents[j].pos <= mv.to);
+    }
 }
 
-pub fn part2(input: &Input) -> u64 {
-    // (pos, size, id)
-    let mut files = Vec::new();
-    // (pos, size)
-    let mut spaces = Vec::new();
-
-    let mut pos = 0;
-    let mut blocks = input.iter().peekable();
-    while let Some(block) = blocks.next() {
-        match block {
-            DiskBlock::File(id) => {
-                let size = std::iter::from_fn(|| {
-                    blocks
-                        .by_ref()
-                        .next_if(|b| matches!(b, DiskBlock::File(id2) if id2 == id))
-                })
-                .count()
-                    + 1;
-                files.push((pos, size, id));
-                pos += size;
+// Serialising and defragmenting, for stress testing the compactions.
+impl Disk {
+    #[allow(dead_code)]
+    pub fn extents(&self) -> &[Extent] {
+        &self.extents
+    }
+
+    /// The inverse of `from_dense`, if the layout can be written that way:
+    /// files in id order, each in one piece, with no run longer than 9.
+    /// Free space after the last file is left off, it doesn't change anything.
+    ///
+    /// Compacting moves files out of id order, and compacting blocks splits them too,
+    /// so neither result can be written back. The error says which file is out of place.
+    #[allow(dead_code)]
+    pub fn to_dense(&self) -> Result<String, String> {
+        let digit = |n: usize, pos: usize| {
+            u32::try_from(n)
+                .ok()
+                .and_then(|n| char::from_digit(n, 10))
+                .ok_or_else(|| format!("run of {} blocks at {} is longer than 9", n, pos))
+        };
+
+        let mut dense = String::new();
+        let mut end = 0;
+        for (i, e) in self.extents.iter().enumerate() {
+            if e.id != i as u64 {
+                let problem = if self.extents[..i].iter().any(|p| p.id == e.id) {
+                    "is in more than one piece".to_string()
+                } else {
+                    format!("is where file {} should be", i)
+                };
+                return Err(format!(
+                    "file {} at {} {}, but the dense format has each file in one piece in id order",
+                    e.id, e.pos, problem
+                ));
             }
-            DiskBlock::FreeSpace => {
-                let size = std::iter::from_fn(|| {
-                    blocks
-                        .by_ref()
-                        .next_if(|b| matches!(b, DiskBlock::FreeSpace))
-                })
-                .count()
-                    + 1;
-                spaces.push((pos, size));
-                pos += size;
+            if i > 0 {
+                dense.push(digit(e.pos - end, end)?);
+            } else if e.pos != 0 {
+                return Err(format!(
+                    "file 0 starts at {}, but the dense format starts with it",
+                    e.pos
+                ));
             }
+            dense.push(digit(e.len, e.pos)?);
+            end = e.pos + e.len;
+        }
+
+        Ok(dense)
+    }
+
+    /// Packs every file into one piece at the start of the disk with no gaps,
+    /// keeping them in the order their first blocks appear.
+    #[allow(dead_code)]
+    pub fn defragment(&self) -> Disk {
+        let mut order = Vec::new();
+        let mut lens = HashMap::new();
+        for e in &self.extents {
+            *lens.entry(e.id).or_insert_with(|| {
+                order.push(e.id);
+                0
+            }) += e.len;
+        }
+
+        let mut pos = 0;
+        let extents = order
+            .into_iter()
+            .map(|id| {
+                let e = Extent {
+                    id,
+                    pos,
+                    len: lens[&id],
+                };
+                pos += e.len;
+                e
+            })
+            .collect();
+
+        Disk {
+            extents,
+            size: self.size,
         }
     }
+}
+
+/// Prints every move of a compaction along with the disk after it.
+/// Big disks only show the blocks around where the move came from and went to.
+pub fn replay(disk: &Disk, whole_files: bool) {
+    const WIDTH: usize = 80;
+
+    let show = |disk: &Disk, around: usize| {
+        let start = around.saturating_sub(WIDTH / 2);
+        println!("{:>8} {}", start, disk.render_window(start..start + WIDTH));
+    };
+
+    let mut moves = Vec::new();
+    let compacted = if whole_files {
+        disk.compact_files_with(|mv| moves.push(mv))
+    } else {
+        disk.compact_blocks_with(|mv| moves.push(mv))
+    };
+
+    let mut disk = disk.clone();
+    println!("{}", disk.render_window(0..WIDTH));
+    for (i, mv) in moves.iter().enumerate() {
+        disk.apply(*mv);
+
+        println!(
+            "\nmove {}: file {} x{} from {} to {}",
+            i + 1,
+            mv.id,
+            mv.len,
+            mv.from,
+            mv.to
+        );
+        if disk.size() <= WIDTH {
+            println!("{}", disk.render());
+        } else {
+            show(&disk, mv.to);
+            show(&disk, mv.from);
+        }
+    }
+
+    println!("\n{} moves, checksum {}", moves.len(), compacted.checksum());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Reverse, collections::BinaryHeap};
+
+    use super::*;
+    use crate::util::rng::Rng;
+
+    const EXAMPLE: &str = "2333133121414131402";
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(part1(&parse(EXAMPLE)), 1928);
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(part2(&parse(EXAMPLE)), 2858);
+    }
+
+    #[test]
+    fn test_dense() {
+        let disk = parse("12345\n");
+        assert_eq!(disk.size(), 15);
+        assert_eq!(disk.gaps().collect::<Vec<_>>(), vec![1..3, 6..10]);
+        assert_eq!(disk.to_dense().as_deref(), Ok("12345"));
+        assert_eq!(parse(EXAMPLE).to_dense().as_deref(), Ok(EXAMPLE));
+
+        // File 2 moves in ahead of file 1.
+        assert_eq!(
+            disk.compact_blocks().to_dense(),
+            Err("file 2 at 1 is where file 1 should be, \
+                but the dense format has each file in one piece in id order"
+                .to_string())
+        );
+        let e = parse(EXAMPLE).compact_files().to_dense().unwrap_err();
+        assert!(e.starts_with("file 9 at 2 is where file 1 should be"));
+
+        let mut split = parse("221");
+        split.apply(Move {
+            id: 0,
+            from: 1,
+            to: 2,
+            len: 1,
+        });
+        let e = split.to_dense().unwrap_err();
+        assert!(e.starts_with("file 0 at 2 is in more than one piece"));
+
+        let defragmented = disk.defragment();
+        assert_eq!(defragmented.to_dense().as_deref(), Ok("10305"));
+        assert_eq!(defragmented.gaps().collect::<Vec<_>>(), vec![9..15]);
+
+        // File 1 moving up leaves two runs of free space joined together.
+        let mut long_gap = parse("19191");
+        long_gap.apply(Move {
+            id: 1,
+            from: 10,
+            to: 1,
+            len: 1,
+        });
+        assert_eq!(
+            long_gap.to_dense(),
+            Err("run of 18 blocks at 2 is longer than 9".to_string())
+        );
+    }
+
+    #[test]
+    fn test_empty_files() {
+        // File 1 is empty, so the free space either side of it is one run that file 2 fits in.
+        let disk = parse("12035\n");
+        assert_eq!(disk.gaps().collect::<Vec<_>>(), vec![1..6]);
+
+        let compacted = disk.compact_files();
+        let file2 = compacted.extents().iter().find(|e| e.id == 2).unwrap();
+        assert_eq!(file2.pos, 1);
+        assert_eq!(compacted.checksum(), 2 * (1 + 2 + 3 + 4 + 5));
+        assert_eq!(compacted.gaps().collect::<Vec<_>>(), vec![6..11]);
+    }
+
+    #[test]
+    fn test_render() {
+        let disk = parse(EXAMPLE);
+        assert_eq!(disk.render(), "00...111...2...333.44.5555.6666.777.888899");
+        assert_eq!(disk.render_window(10..20), ".2...333.4");
+        assert_eq!(disk.render_window(40..100), "99");
 
-    for (file_pos, file_size, _) in files.iter_mut().rev() {
-        for (space_pos, space_size) in spaces.iter_mut() {
-            if space_pos < file_pos && space_size >= file_size {
-                *file_pos = *space_pos;
+        assert_eq!(
+            disk.compact_blocks().render(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(
+            disk.compact_files().render(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+    }
 
-                *space_pos += *file_size;
-                *space_size -= *file_size;
+    #[test]
+    fn test_replay_moves() {
+        let disk = parse(EXAMPLE);
 
-                break;
+        let mut moves = Vec::new();
+        let compacted = disk.compact_files_with(|mv| moves.push(mv));
+        assert_eq!(
+            moves[0],
+            Move {
+                id: 9,
+                from: 40,
+                to: 2,
+                len: 2
             }
+        );
+
+        let mut replayed = disk.clone();
+        let mut states = Vec::new();
+        for mv in &moves {
+            replayed.apply(*mv);
+            states.push(replayed.render());
         }
+        assert_eq!(states[0], "0099.111...2...333.44.5555.6666.777.8888..");
+        assert_eq!(replayed.render(), compacted.render());
+
+        let mut moves = Vec::new();
+        let compacted = disk.compact_blocks_with(|mv| moves.push(mv));
+        assert_eq!(
+            moves[..3],
+            [
+                Move {
+                    id: 9,
+                    from: 40,
+                    to: 2,
+                    len: 2
+                },
+                Move {
+                    id: 8,
+                    from: 39,
+                    to: 4,
+                    len: 1
+                },
+                Move {
+                    id: 8,
+                    from: 36,
+                    to: 8,
+                    len: 3
+                },
+            ]
+        );
+
+        let mut replayed = disk.clone();
+        for mv in moves {
+            replayed.apply(mv);
+        }
+        assert_eq!(replayed, compacted);
     }
 
-    files
-        .into_iter()
-        .map(|(pos, size, id)| (pos..pos + size).sum::<usize>() as u64 * id)
-        .sum()
+    // Random dense disk maps, like the puzzle's but any length.
+    fn random_dense(seed: u64, len: usize) -> String {
+        let mut rng = Rng::new(seed);
+        (0..len)
+            .map(|i| {
+                // Files are never empty in real inputs.
+                let min = if i % 2 == 0 { 1 } else { 0 };
+                char::from_digit(min + rng.below(10 - min as u64) as u32, 10).unwrap()
+            })
+            .collect()
+    }
+
+    // One entry per block, for checking against.
+    fn blocks(disk: &Disk) -> Vec<Option<u64>> {
+        let mut blocks = vec![None; disk.size()];
+        for e in disk.extents() {
+            for b in &mut blocks[e.pos..e.pos + e.len] {
+                assert_eq!(*b, None, "overlapping extents");
+                *b = Some(e.id);
+            }
+        }
+        blocks
+    }
+
+    // Free space as one min-heap of starts per gap length, which only works
+    // while gaps are short, to check the `RangeSet` against.
+    fn compact_files_with_heaps(disk: &Disk) -> u64 {
+        let mut free = vec![BinaryHeap::new(); 10];
+        for gap in disk.gaps() {
+            free[gap.len()].push(Reverse(gap.start));
+        }
+
+        disk.extents()
+            .iter()
+            .rev()
+            .map(|e| {
+                let mut pos = e.pos;
+                let fit = (e.len..10)
+                    .filter_map(|len| free[len].peek().map(|Reverse(start)| (*start, len)))
+                    .min();
+                if let Some((start, len)) = fit.filter(|(start, _)| *start < pos) {
+                    free[len].pop();
+                    if len > e.len {
+                        free[len - e.len].push(Reverse(start + e.len));
+                    }
+                    pos = start;
+                }
+                let (pos, len) = (pos as u64, e.len as u64);
+                e.id * (len * pos + len * len.saturating_sub(1) / 2)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_stress() {
+        for (seed, len) in [(1, 99), (2, 1000), (3, 100_000)] {
+            let dense = random_dense(seed, len);
+            let disk = parse(&dense);
+            // Ends in a free run half the time, which doesn't get written back.
+            let written = disk.to_dense().unwrap();
+            assert!(dense.starts_with(&written) && dense.len() - written.len() <= 1);
+            assert_eq!(parse(&written).extents(), disk.extents());
+
+            let before = blocks(&disk);
+            let n_used = before.iter().flatten().count();
+
+            // Block by block leaves everything packed at the start, the ids in reverse from the back.
+            let compacted = blocks(&disk.compact_blocks());
+            assert!(compacted[..n_used].iter().all(|b| b.is_some()));
+            assert!(compacted[n_used..].iter().all(|b| b.is_none()));
+            let mut used = before.iter().flatten().copied().collect::<Vec<_>>();
+            let mut moved = compacted.iter().flatten().copied().collect::<Vec<_>>();
+            used.sort();
+            moved.sort();
+            assert_eq!(used, moved);
+
+            let whole = disk.compact_files();
+            blocks(&whole);
+            assert_eq!(whole.extents().len(), disk.extents().len());
+            assert_eq!(whole.checksum(), compact_files_with_heaps(&disk));
+
+            let defragmented = disk.defragment();
+            let written = defragmented.to_dense().unwrap();
+            assert_eq!(parse(&written).extents(), defragmented.extents());
+            assert_eq!(parse(&written).checksum(), defragmented.checksum());
+        }
+    }
 }
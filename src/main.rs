@@ -1,12 +1,25 @@
 use std::{
+    fmt::Display,
     fs::read_to_string,
     path::{Path, PathBuf},
     time::Instant,
 };
 
+// Helpers and the days' APIs are kept complete. Anything that neither an answer nor a
+// command uses yet carries its own `allow(dead_code)`, and only test fixtures are `cfg(test)`.
 mod util {
+    pub mod components;
+    pub mod dsu;
     pub mod grid;
+    pub mod math;
+    pub mod memo;
+    pub mod parse;
     pub mod position;
+    pub mod ranges;
+    #[cfg(test)]
+    pub mod rng;
+    pub mod topo;
+    pub mod word_search;
 }
 
 // Day 0 = template.
@@ -41,6 +54,57 @@ mod day24;
 mod day25;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [command, rest @ ..] = args.as_slice() {
+        match command.as_str() {
+            // day8-antinodes <part1|part2> [input]
+            "day8-antinodes" => {
+                if let Some((all, input)) = command_args(rest, ["part1", "part2"], "day8") {
+                    let harmonics = if all {
+                        day8::Harmonics::All
+                    } else {
+                        day8::Harmonics::part1()
+                    };
+                    day8::print_antinodes(&day8::parse(&input), &harmonics);
+                }
+            }
+            // day9-replay <part1|part2> [input]
+            "day9-replay" => {
+                if let Some((whole_files, input)) = command_args(rest, ["part1", "part2"], "day9") {
+                    day9::replay(&day9::parse(&input), whole_files);
+                }
+            }
+            // day11-blinks <blinks> [input]
+            "day11-blinks" => match rest.first().map(|arg| arg.parse::<u32>()) {
+                Some(Ok(blinks)) => {
+                    if let Some(input) = read_input(rest.get(1), "day11") {
+                        day11::print_blinks(&day11::parse(&input), blinks);
+                    }
+                }
+                _ => println!("expected a number of blinks"),
+            },
+            // day14-frame <text|pgm> [input]
+            "day14-frame" => {
+                if let Some((pgm, input)) = command_args(rest, ["text", "pgm"], "day14") {
+                    day14::print_easter_egg(&day14::parse(&input), pgm);
+                }
+            }
+            // day15-replay <part1|part2> [input]
+            "day15-replay" => {
+                if let Some((wide, input)) = command_args(rest, ["part1", "part2"], "day15") {
+                    let config = if wide {
+                        day15::Config::PART2
+                    } else {
+                        day15::Config::PART1
+                    };
+                    day15::replay(&day15::parse(&input), &config);
+                }
+            }
+            _ => println!("unknown command {}", command),
+        }
+        return;
+    }
+
     println!("--- AoC 2024! ---\n");
 
     let start = Instant::now();
@@ -69,12 +133,69 @@ fn main() {
     println!("\nCompleted {} days in {:?}.", solutions().len(), duration);
 }
 
+/// Reads `<first|second> [input]`, returning whether it was the second choice along with the input.
+/// The choice defaults to the first and the input to the day's usual file.
+fn command_args(args: &[String], choices: [&str; 2], day: &str) -> Option<(bool, String)> {
+    let second = match args.first() {
+        None => false,
+        Some(arg) if arg == choices[0] => false,
+        Some(arg) if arg == choices[1] => true,
+        Some(other) => {
+            println!("expected {} or {}, got {}", choices[0], choices[1], other);
+            return None;
+        }
+    };
+
+    read_input(args.get(1), day).map(|input| (second, input))
+}
+
+/// Reads the input at `path`, or the day's usual file if there isn't one.
+fn read_input(path: Option<&String>, day: &str) -> Option<String> {
+    let path = path.map_or(
+        Path::new("inputs").join(day).with_extension("txt"),
+        PathBuf::from,
+    );
+    match read_to_string(&path) {
+        Ok(input) => Some(input),
+        Err(e) => {
+            println!("failed to load {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
 struct Solution {
     day: &'static str,
     input: PathBuf,
     run: fn(String) -> (String, String),
 }
 
+/// What a part can return: the answer itself, or an error explaining why there isn't one.
+trait Answer {
+    fn answer(&self) -> String;
+}
+
+macro_rules! plain_answer {
+    ($($t:ty),*) => {$(
+        impl Answer for $t {
+            fn answer(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+
+plain_answer!(u32, u64, String);
+
+impl<T: Answer, E: Display> Answer for Result<T, E> {
+    fn answer(&self) -> String {
+        match self {
+            Ok(answer) => answer.answer(),
+            Err(e) => format!("error ({})", e),
+        }
+    }
+}
+
 macro_rules! solution {
     ($day:tt) => {{
         let day = stringify!($day);
@@ -83,7 +204,7 @@ macro_rules! solution {
             use $day::*;
 
             let input = parse(&data);
-            (part1(&input).to_string(), part2(&input).to_string())
+            (part1(&input).answer(), part2(&input).answer())
         };
 
         Solution { day, input, run }
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use super::{
+    grid::Grid,
+    position::{Compass, Position},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Orthogonal neighbours only.
+    Four,
+    /// Orthogonal and diagonal neighbours.
+    #[allow(dead_code)]
+    Eight,
+}
+
+impl Connectivity {
+    fn directions(self) -> &'static [Compass] {
+        match self {
+            Connectivity::Four => &[Compass::N, Compass::E, Compass::S, Compass::W],
+            Connectivity::Eight => &Compass::ALL8,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Component {
+    pub cells: Vec<Position>,
+    /// Number of cell edges that don't border another cell of the same component.
+    /// Always counted orthogonally, even for eight-connected components.
+    pub perimeter: u64,
+    /// Inclusive top-left and bottom-right corners.
+    pub bounding_box: (Position, Position),
+}
+
+impl Component {
+    pub fn area(&self) -> u64 {
+        self.cells.len() as u64
+    }
+}
+
+pub struct Components {
+    /// Index into `components` for every cell of the grid.
+    pub labels: Grid<usize>,
+    pub components: Vec<Component>,
+}
+
+/// Labels every connected component of `grid`, where two neighbouring cells
+/// are in the same component when `same_region` says so.
+///
+/// Components are numbered in row-major order of their first cell.
+pub fn label<E, F>(grid: &Grid<E>, connectivity: Connectivity, same_region: F) -> Components
+where
+    F: Fn(&E, &E) -> bool,
+{
+    const UNLABELLED: usize = usize::MAX;
+
+    let mut labels = Grid::new(grid.n_rows() as usize, grid.n_cols() as usize, UNLABELLED);
+    let mut components = Vec::new();
+
+    let mut q = VecDeque::new();
+    for row in 0..grid.n_rows() {
+        for col in 0..grid.n_cols() {
+            let start = Position::new(row, col);
+            if *labels.get(start).unwrap() != UNLABELLED {
+                continue;
+            }
+
+            let label = components.len();
+            labels.set(start, label);
+            q.push_back(start);
+
+            let mut cells = Vec::new();
+            while let Some(p) = q.pop_front() {
+                cells.push(p);
+
+                let e = grid.get(p).unwrap();
+                for d in connectivity.directions() {
+                    let n = p.moved_in(*d);
+                    let Some(ne) = grid.get(n) else {
+                        continue;
+                    };
+                    if *labels.get(n).unwrap() == UNLABELLED && same_region(e, ne) {
+                        labels.set(n, label);
+                        q.push_back(n);
+                    }
+                }
+            }
+
+            components.push(Component {
+                perimeter: 0,
+                bounding_box: bounding_box(&cells),
+                cells,
+            });
+        }
+    }
+
+    // Done after labelling so the perimeter of eight-connected components
+    // doesn't count edges shared with a diagonally connected cell's neighbours.
+    for (label, component) in components.iter_mut().enumerate() {
+        component.perimeter = component
+            .cells
+            .iter()
+            .map(|p| {
+                Connectivity::Four
+                    .directions()
+                    .iter()
+                    .filter(|d| labels.get(p.moved_in(**d)) != Some(&label))
+                    .count() as u64
+            })
+            .sum();
+    }
+
+    Components { labels, components }
+}
+
+/// Shorthand for the common case of regions being runs of equal cells.
+pub fn label_equal<E: PartialEq>(grid: &Grid<E>, connectivity: Connectivity) -> Components {
+    label(grid, connectivity, |a, b| a == b)
+}
+
+fn bounding_box(cells: &[Position]) -> (Position, Position) {
+    cells.iter().fold(
+        (
+            Position::new(i32::MAX, i32::MAX),
+            Position::new(i32::MIN, i32::MIN),
+        ),
+        |(min, max), p| {
+            (
+                Position::new(min.row.min(p.row), min.col.min(p.col)),
+                Position::new(max.row.max(p.row), max.col.max(p.col)),
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_four_connected() {
+        let grid: Grid<char> = "AAB\nABB\nCCB".into();
+        let components = label_equal(&grid, Connectivity::Four);
+
+        assert_eq!(components.components.len(), 3);
+
+        let a = &components.components[0];
+        assert_eq!(a.area(), 3);
+        assert_eq!(a.perimeter, 8);
+        assert_eq!(a.bounding_box, (Position::new(0, 0), Position::new(1, 1)));
+
+        let b = &components.components[1];
+        assert_eq!(b.area(), 4);
+        assert_eq!(b.perimeter, 10);
+
+        assert_eq!(components.labels.get(Position::new(2, 1)), Some(&2));
+    }
+
+    #[test]
+    fn test_eight_connected() {
+        let grid: Grid<char> = "X.X\n.X.\n..X".into();
+
+        let four = label(&grid, Connectivity::Four, |a, b| a == b && *a == 'X');
+        let eight = label(&grid, Connectivity::Eight, |a, b| a == b && *a == 'X');
+
+        // With four-connectivity every X and every . is alone (the dots aren't joined by the predicate).
+        assert_eq!(four.components.len(), 9);
+
+        let xs = eight
+            .components
+            .iter()
+            .filter(|c| grid.is(c.cells[0], 'X'))
+            .collect::<Vec<_>>();
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].area(), 4);
+        assert_eq!(xs[0].perimeter, 16);
+    }
+}
@@ -0,0 +1,246 @@
+use super::position::Position;
+
+/// Disjoint set union over `0..n`, with path compression and union by rank.
+#[derive(Clone, Debug)]
+pub struct Dsu {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    size: Vec<usize>,
+    count: usize,
+}
+
+impl Dsu {
+    pub fn new(n: usize) -> Self {
+        Dsu {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+            size: vec![1; n],
+            count: n,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Representative of `x`'s set.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        // Point everything on the way straight at the root.
+        let mut x = x;
+        while self.parent[x] != root {
+            (x, self.parent[x]) = (self.parent[x], root);
+        }
+
+        root
+    }
+
+    /// Merges the sets of `a` and `b`, returning false if they were already the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+
+        if self.rank[a] < self.rank[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        if self.rank[a] == self.rank[b] {
+            self.rank[a] += 1;
+        }
+
+        self.count -= 1;
+        true
+    }
+
+    pub fn same(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Size of `x`'s set.
+    #[allow(dead_code)]
+    pub fn size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+
+    /// Number of disjoint sets.
+    #[allow(dead_code)]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Connectivity of the cells of a grid as they're added one at a time,
+/// where added cells join up with their orthogonal neighbours.
+///
+/// Cells can't be removed, so "when does X get cut off" questions are answered
+/// by adding the cells back in reverse.
+#[derive(Clone, Debug)]
+pub struct GridDsu {
+    dsu: Dsu,
+    n_rows: i32,
+    n_cols: i32,
+    added: Vec<bool>,
+    count: usize,
+}
+
+impl GridDsu {
+    /// Starts with no cells added.
+    pub fn new(n_rows: i32, n_cols: i32) -> Self {
+        let n = (n_rows * n_cols) as usize;
+        GridDsu {
+            dsu: Dsu::new(n),
+            n_rows,
+            n_cols,
+            added: vec![false; n],
+            count: 0,
+        }
+    }
+
+    pub fn index(&self, pos: Position) -> Option<usize> {
+        if (0..self.n_rows).contains(&pos.row) && (0..self.n_cols).contains(&pos.col) {
+            Some((pos.row * self.n_cols + pos.col) as usize)
+        } else {
+            None
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn position(&self, index: usize) -> Position {
+        let index = index as i32;
+        Position::new(index / self.n_cols, index % self.n_cols)
+    }
+
+    /// Does nothing for cells out of bounds or already added.
+    pub fn add(&mut self, pos: Position) {
+        let Some(i) = self.index(pos) else {
+            return;
+        };
+        if self.added[i] {
+            return;
+        }
+        self.added[i] = true;
+        self.count += 1;
+
+        for n in pos.neighbours() {
+            if let Some(j) = self.index(n) {
+                if self.added[j] && self.dsu.union(i, j) {
+                    self.count -= 1;
+                }
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, pos: Position) -> bool {
+        self.index(pos).is_some_and(|i| self.added[i])
+    }
+
+    /// False if either cell hasn't been added.
+    pub fn connected(&mut self, a: Position, b: Position) -> bool {
+        match (self.index(a), self.index(b)) {
+            (Some(i), Some(j)) if self.added[i] && self.added[j] => self.dsu.same(i, j),
+            _ => false,
+        }
+    }
+
+    /// Number of cells in `pos`'s component, 0 if it hasn't been added.
+    #[allow(dead_code)]
+    pub fn component_size(&mut self, pos: Position) -> usize {
+        match self.index(pos) {
+            Some(i) if self.added[i] => self.dsu.size(i),
+            _ => 0,
+        }
+    }
+
+    /// Number of components among the added cells.
+    #[allow(dead_code)]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dsu() {
+        let mut dsu = Dsu::new(6);
+        assert_eq!(dsu.count(), 6);
+
+        assert!(dsu.union(0, 1));
+        assert!(dsu.union(2, 3));
+        assert!(dsu.union(1, 3));
+        assert!(!dsu.union(0, 2));
+
+        assert_eq!(dsu.count(), 3);
+        assert!(dsu.same(0, 3));
+        assert!(!dsu.same(0, 4));
+        assert_eq!(dsu.size(2), 4);
+        assert_eq!(dsu.size(5), 1);
+    }
+
+    #[test]
+    fn test_grid_dsu() {
+        // .#.
+        // .#.
+        // ...
+        let mut grid = GridDsu::new(3, 3);
+        let wall = [Position::new(0, 1), Position::new(1, 1)];
+        for row in 0..3 {
+            for col in 0..3 {
+                let p = Position::new(row, col);
+                if !wall.contains(&p) {
+                    grid.add(p);
+                }
+            }
+        }
+
+        assert_eq!(grid.count(), 1);
+        assert_eq!(grid.component_size(Position::new(0, 0)), 7);
+        assert!(grid.connected(Position::new(0, 0), Position::new(0, 2)));
+        assert!(!grid.connected(Position::new(0, 0), Position::new(0, 1)));
+        assert_eq!(grid.index(Position::new(3, 0)), None);
+        assert_eq!(
+            grid.position(grid.index(Position::new(2, 1)).unwrap()),
+            Position::new(2, 1)
+        );
+
+        grid.add(Position::new(1, 1));
+        assert_eq!(grid.count(), 1);
+        assert_eq!(grid.component_size(Position::new(1, 1)), 8);
+    }
+
+    #[test]
+    fn test_grid_dsu_counts_components() {
+        // Checkerboard, every cell alone until the gaps are filled.
+        let mut grid = GridDsu::new(4, 4);
+        for row in 0..4 {
+            for col in 0..4 {
+                if (row + col) % 2 == 0 {
+                    grid.add(Position::new(row, col));
+                }
+            }
+        }
+        assert_eq!(grid.count(), 8);
+
+        grid.add(Position::new(0, 1));
+        assert_eq!(grid.count(), 6);
+        grid.add(Position::new(0, 1));
+        assert_eq!(grid.count(), 6);
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Write;
+use std::fmt::{Display, Write};
 
 use super::position::Position;
 
@@ -42,6 +42,14 @@ impl<E> Grid<E> {
         self.0[0].len() as i32
     }
 
+    /// Inclusive top-left and bottom-right corners.
+    pub fn bounds(&self) -> (Position, Position) {
+        (
+            Position::new(0, 0),
+            Position::new(self.n_rows() - 1, self.n_cols() - 1),
+        )
+    }
+
     pub fn get(&self, pos: Position) -> Option<&E> {
         let row: usize = pos.row.try_into().ok()?;
         let col: usize = pos.col.try_into().ok()?;
@@ -63,6 +71,30 @@ impl<E> Grid<E> {
             }
         }
     }
+
+    /// A picture of the grid, one line per row with each cell drawn by `draw`.
+    pub fn render<D: Display>(&self, draw: impl FnMut(Position, &E) -> D) -> String {
+        self.render_separated("", draw)
+    }
+
+    /// `render` with `separator` between the cells on a row, for when they're more than a char.
+    pub fn render_separated<D: Display>(
+        &self,
+        separator: &str,
+        mut draw: impl FnMut(Position, &E) -> D,
+    ) -> String {
+        let mut out = String::new();
+        for (row, cells) in self.0.iter().enumerate() {
+            for (col, e) in cells.iter().enumerate() {
+                if col > 0 {
+                    out.push_str(separator);
+                }
+                write!(out, "{}", draw(Position::new(row as i32, col as i32), e)).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
 }
 
 impl<E: Clone> Grid<E> {
@@ -82,10 +114,6 @@ impl<E: PartialEq> Grid<E> {
     pub fn is(&self, pos: Position, element: E) -> bool {
         self.get(pos).map(|e| *e == element).unwrap_or(false)
     }
-
-    pub fn is_row_col(&self, row: i32, col: i32, element: E) -> bool {
-        self.is(Position::new(row, col), element)
-    }
 }
 
 impl<E: Clone + PartialEq> Grid<E> {
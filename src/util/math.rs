@@ -0,0 +1,263 @@
+use super::position::Coord;
+
+/// Always non-negative, `gcd(0, 0) == 0`.
+pub fn gcd<T: Coord>(mut a: T, mut b: T) -> T {
+    while b != T::ZERO {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+/// Always non-negative, `lcm(0, x) == 0`.
+#[allow(dead_code)]
+pub fn lcm<T: Coord>(a: T, b: T) -> T {
+    if a == T::ZERO || b == T::ZERO {
+        return T::ZERO;
+    }
+    (a / gcd(a, b) * b).abs()
+}
+
+pub fn checked_lcm(a: i128, b: i128) -> Option<i128> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / gcd(a, b)).checked_mul(b)?.checked_abs()
+}
+
+/// `(g, x, y)` such that `a*x + b*y == g == gcd(a, b)`.
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// `x` in `0..m` with `a*x == 1 (mod m)`, if `a` and `m` are coprime.
+#[allow(dead_code)]
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    (g == 1).then(|| x.rem_euclid(m))
+}
+
+/// `a*b mod m` without overflowing, even when `a*b` doesn't fit in an `i128`.
+pub fn mod_mul(a: i128, b: i128, m: i128) -> i128 {
+    let (mut a, mut b) = (a.rem_euclid(m), b.rem_euclid(m));
+    if let Some(x) = a.checked_mul(b) {
+        return x % m;
+    }
+
+    // Double-and-add, each step stays below 2m.
+    let mut result = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a, m);
+        }
+        a = add_mod(a, a, m);
+        b >>= 1;
+    }
+    result
+}
+
+fn add_mod(a: i128, b: i128, m: i128) -> i128 {
+    // Both are in 0..m so `a - (m - b)` can't overflow.
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+/// `base^exp mod m`, in `0..m`.
+#[allow(dead_code)]
+pub fn mod_pow(base: i128, mut exp: u128, m: i128) -> i128 {
+    let mut base = base.rem_euclid(m);
+    let mut result = 1 % m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mod_mul(result, base, m);
+        }
+        base = mod_mul(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrtError {
+    /// The congruences contradict each other.
+    NoSolution,
+    /// The combined modulus doesn't fit in an `i128`.
+    Overflow,
+}
+
+/// Solves `x == r_i (mod m_i)` for every `(r_i, m_i)`, where the moduli don't have to be coprime.
+/// Returns the smallest non-negative `x` and the modulus of the combined congruence.
+pub fn crt(congruences: &[(i128, i128)]) -> Result<(i128, i128), CrtError> {
+    let mut x: i128 = 0;
+    let mut m: i128 = 1;
+
+    for &(r, n) in congruences {
+        assert!(n > 0, "moduli must be positive");
+        let r = r.rem_euclid(n);
+
+        // x + m*k == r (mod n)  =>  m*k == r - x (mod n)
+        let (g, p, _) = extended_gcd(m, n);
+        let diff = r - x.rem_euclid(n);
+        if diff % g != 0 {
+            return Err(CrtError::NoSolution);
+        }
+
+        let n_g = n / g;
+        let k = mod_mul(diff / g, p, n_g);
+
+        let new_m = checked_lcm(m, n).ok_or(CrtError::Overflow)?;
+        x = (x + mod_mul(m, k, new_m)).rem_euclid(new_m);
+        m = new_m;
+    }
+
+    Ok((x, m))
+}
+
+/// Largest `r` with `r*r <= n`.
+#[allow(dead_code)]
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // Newton's method from an overestimate, decreasing until it settles.
+    let mut x = 1u128 << (n.ilog2() / 2 + 1);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_gcd(a: i128, b: i128) -> i128 {
+        (1..=a.abs().max(b.abs()))
+            .rev()
+            .find(|d| a % d == 0 && b % d == 0)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        for a in -30..=30i128 {
+            for b in -30..=30i128 {
+                let g = gcd(a, b);
+                assert_eq!(g, brute_gcd(a, b));
+
+                let l = lcm(a, b);
+                if a != 0 && b != 0 {
+                    let brute = (1..).find(|x| x % a == 0 && x % b == 0).unwrap();
+                    assert_eq!(l, brute);
+                } else {
+                    assert_eq!(l, 0);
+                }
+                assert_eq!(checked_lcm(a, b), Some(l));
+
+                let (g2, x, y) = extended_gcd(a, b);
+                assert_eq!(g2, g);
+                assert_eq!(a * x + b * y, g);
+            }
+        }
+
+        assert_eq!(gcd(12i32, -18), 6);
+        assert_eq!(checked_lcm(i128::MAX, i128::MAX - 1), None);
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        for m in 1..=30i128 {
+            for a in -30..=30i128 {
+                let brute = (0..m).find(|x| (a * x).rem_euclid(m) == 1 % m);
+                let inv = mod_inverse(a, m);
+                assert_eq!(inv.is_some(), brute.is_some(), "a={} m={}", a, m);
+                if let Some(inv) = inv {
+                    assert!((0..m).contains(&inv));
+                    assert_eq!((a * inv).rem_euclid(m), 1 % m);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mod_pow() {
+        for m in 1..=20i128 {
+            for base in -10..=10i128 {
+                let mut brute = 1 % m;
+                for exp in 0..20u128 {
+                    assert_eq!(mod_pow(base, exp, m), brute);
+                    brute = (brute * base).rem_euclid(m);
+                }
+            }
+        }
+
+        // Needs the overflow-safe multiply.
+        let p = (1i128 << 89) - 1;
+        assert_eq!(mod_pow(3, (p - 1) as u128, p), 1);
+        assert_eq!(mod_mul(p - 1, p - 1, p), 1);
+    }
+
+    #[test]
+    fn test_crt() {
+        for m1 in 1..=12i128 {
+            for m2 in 1..=12i128 {
+                for r1 in 0..m1 {
+                    for r2 in 0..m2 {
+                        let l = lcm(m1, m2);
+                        let brute = (0..l).find(|x| x % m1 == r1 && x % m2 == r2);
+                        match crt(&[(r1, m1), (r2, m2)]) {
+                            Ok((x, m)) => {
+                                assert_eq!(Some(x), brute);
+                                assert_eq!(m, l);
+                            }
+                            Err(e) => {
+                                assert_eq!(e, CrtError::NoSolution);
+                                assert_eq!(brute, None);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        assert_eq!(crt(&[]), Ok((0, 1)));
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Ok((23, 105)));
+        assert_eq!(crt(&[(-1, 101), (-1, 103)]), Ok((101 * 103 - 1, 101 * 103)));
+        assert_eq!(
+            crt(&[(0, i128::MAX), (0, i128::MAX - 1)]),
+            Err(CrtError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for n in 0..10_000u128 {
+            let r = isqrt(n);
+            assert!(r * r <= n && (r + 1) * (r + 1) > n, "n={}", n);
+        }
+
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(isqrt(1 << 100), 1 << 50);
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Cache for recursive solvers, keyed by whatever the arguments that vary are.
+///
+/// The usual shape is:
+///
+/// ```ignore
+/// fn count(memo: &mut Memo<u64, u64>, n: u64) -> u64 {
+///     memo.get_or_insert_with(n, |memo| if n < 2 { 1 } else { count(memo, n - 1) + count(memo, n - 2) })
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Memo<K, V> {
+    cache: HashMap<K, V>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct MemoStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: usize,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Memo {
+            cache: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Memo<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts as a hit or a miss.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        match self.cache.get(key) {
+            Some(v) => {
+                self.hits += 1;
+                Some(v.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Whether `key` is cached, without counting as a hit or a miss.
+    pub fn contains(&self, key: &K) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns `value` back so it can be the last expression of the solver.
+    pub fn insert(&mut self, key: K, value: V) -> V {
+        self.cache.insert(key, value.clone());
+        value
+    }
+
+    /// Cached value for `key`, or computes and caches it with `f`.
+    /// `f` is given the memo so it can recurse.
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce(&mut Self) -> V) -> V {
+        if let Some(v) = self.get(&key) {
+            return v;
+        }
+
+        let v = f(self);
+        self.insert(key, v)
+    }
+
+    #[allow(dead_code)]
+    pub fn stats(&self) -> MemoStats {
+        MemoStats {
+            hits: self.hits,
+            misses: self.misses,
+            size: self.cache.len(),
+        }
+    }
+
+    /// Empties the cache and resets the stats, for reusing between inputs.
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fib(memo: &mut Memo<u64, u64>, n: u64) -> u64 {
+        memo.get_or_insert_with(n, |memo| {
+            if n < 2 {
+                n
+            } else {
+                fib(memo, n - 1) + fib(memo, n - 2)
+            }
+        })
+    }
+
+    #[test]
+    fn test_memo() {
+        let mut memo = Memo::new();
+        assert_eq!(fib(&mut memo, 90), 2880067194370816120);
+
+        // Each n is computed once, and only the fib(n - 2) calls for n >= 3 find it cached.
+        let stats = memo.stats();
+        assert_eq!(stats.size, 91);
+        assert_eq!(stats.misses, 91);
+        assert_eq!(stats.hits, 88);
+
+        // Only `get` counts.
+        assert!(memo.contains(&90) && !memo.contains(&91));
+        assert_eq!(memo.stats(), stats);
+
+        memo.clear();
+        assert_eq!(memo.stats(), MemoStats::default());
+        assert_eq!(fib(&mut memo, 10), 55);
+    }
+}
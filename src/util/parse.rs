@@ -0,0 +1,357 @@
+use std::{fmt::Display, str::FromStr};
+
+/// Where in the input parsing went wrong.
+/// Columns are byte offsets into the line, lines are only known when the caller adds them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            line: None,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub fn at_line(self, line: usize) -> Self {
+        ParseError {
+            line: Some(line),
+            ..self
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}, column {}: {}", line, self.column, self.message),
+            None => write!(f, "column {}: {}", self.column, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Every integer in `s`, where a `-` directly before the digits makes it negative.
+#[allow(dead_code)]
+pub fn signed_ints<T>(s: &str) -> Result<Vec<T>, ParseError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    ints(s, true)
+}
+
+/// Every run of digits in `s`, ignoring any signs.
+pub fn unsigned_ints<T>(s: &str) -> Result<Vec<T>, ParseError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    ints(s, false)
+}
+
+/// Fails on the first number that doesn't fit in a `T`.
+/// The error's column is within its line, and the line is filled in when `s` has more than one.
+fn ints<T>(s: &str, signed: bool) -> Result<Vec<T>, ParseError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let bytes = s.as_bytes();
+    let multiline = s.contains('\n');
+    let mut out = Vec::new();
+
+    let (mut line, mut line_start) = (1, 0);
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() {
+            if bytes[i] == b'\n' {
+                line += 1;
+                line_start = i + 1;
+            }
+            i += 1;
+            continue;
+        }
+
+        let start = if signed && i > 0 && bytes[i - 1] == b'-' {
+            i - 1
+        } else {
+            i
+        };
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+
+        let n = &s[start..i];
+        match n.parse() {
+            Ok(n) => out.push(n),
+            Err(e) => {
+                let message = format!(
+                    "can't parse {:?} as {}: {}",
+                    n,
+                    std::any::type_name::<T>(),
+                    e
+                );
+                let error = ParseError::new(start - line_start, message);
+                return Err(if multiline {
+                    error.at_line(line)
+                } else {
+                    error
+                });
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Splits on blank lines, the usual way AoC inputs separate their parts.
+pub fn sections(input: &str) -> impl Iterator<Item = &str> {
+    let mut lines = input.split_inclusive('\n');
+    let mut offset = 0;
+    let mut start = None;
+
+    std::iter::from_fn(move || {
+        for l in lines.by_ref() {
+            let line_start = offset;
+            offset += l.len();
+
+            if l.trim_end_matches(['\r', '\n']).is_empty() {
+                if let Some(start) = start.take() {
+                    return Some(input[start..line_start].trim_end_matches(['\r', '\n']));
+                }
+            } else if start.is_none() {
+                start = Some(line_start);
+            }
+        }
+
+        start
+            .take()
+            .map(|start| input[start..].trim_end_matches(['\r', '\n']))
+    })
+}
+
+/// Splits a `key: value` line.
+pub fn key_value(line: &str) -> Option<(&str, &str)> {
+    line.split_once(": ")
+}
+
+/// A scanf-style pattern, where each `{}` captures everything up to the next literal part.
+///
+/// ```ignore
+/// const ROBOT: Pattern = Pattern::new("p={},{} v={},{}");
+/// let (px, py, vx, vy): (i32, i32, i32, i32) = ROBOT.parse("p=0,4 v=3,-3")?;
+/// ```
+///
+/// Two captures can't be next to each other since there'd be no way to tell where one ends.
+#[derive(Clone, Copy, Debug)]
+pub struct Pattern(&'static str);
+
+impl Pattern {
+    pub const fn new(pattern: &'static str) -> Self {
+        Pattern(pattern)
+    }
+
+    /// The captured text and the column it started at.
+    pub fn captures<'s>(&self, s: &'s str) -> Result<Vec<(usize, &'s str)>, ParseError> {
+        let mut parts = self.0.split("{}");
+        let mut captures = Vec::new();
+
+        // First part has no capture before it.
+        let first = parts.next().unwrap();
+        let mut pos = match s.strip_prefix(first) {
+            Some(_) => first.len(),
+            None => return Err(expected(s, 0, first)),
+        };
+
+        for literal in parts {
+            let rest = &s[pos..];
+
+            let len = if literal.is_empty() {
+                rest.len()
+            } else {
+                match rest.find(literal) {
+                    Some(len) => len,
+                    None => return Err(expected(s, pos, literal)),
+                }
+            };
+            if len == 0 {
+                return Err(ParseError::new(pos, "empty capture"));
+            }
+
+            captures.push((pos, &rest[..len]));
+            pos += len + literal.len();
+        }
+
+        if pos != s.len() {
+            return Err(ParseError::new(
+                pos,
+                format!("unexpected trailing {:?}", &s[pos..]),
+            ));
+        }
+
+        Ok(captures)
+    }
+
+    pub fn parse<'s, C: FromCaptures<'s>>(&self, s: &'s str) -> Result<C, ParseError> {
+        C::from_captures(&self.captures(s)?)
+    }
+}
+
+fn expected(s: &str, pos: usize, literal: &str) -> ParseError {
+    ParseError::new(
+        pos,
+        format!("expected {:?}, found {:?}", literal, &s[pos..]),
+    )
+}
+
+/// A single typed `{}` capture.
+pub trait FromCapture<'s>: Sized {
+    fn from_capture(s: &'s str) -> Result<Self, String>;
+}
+
+impl<'s> FromCapture<'s> for &'s str {
+    fn from_capture(s: &'s str) -> Result<Self, String> {
+        Ok(s)
+    }
+}
+
+macro_rules! impl_from_capture {
+    ($($t:ty),+) => {
+        $(
+            impl FromCapture<'_> for $t {
+                fn from_capture(s: &str) -> Result<Self, String> {
+                    s.parse()
+                        .map_err(|e| format!("can't parse {:?} as {}: {}", s, stringify!($t), e))
+                }
+            }
+        )+
+    };
+}
+impl_from_capture!(u8, u32, u64, usize, i32, i64, i128, char, String);
+
+/// All of a pattern's captures, as a tuple.
+pub trait FromCaptures<'s>: Sized {
+    fn from_captures(captures: &[(usize, &'s str)]) -> Result<Self, ParseError>;
+}
+
+macro_rules! impl_from_captures {
+    ($n:literal; $($t:ident),+) => {
+        impl<'s, $($t: FromCapture<'s>),+> FromCaptures<'s> for ($($t,)+) {
+            fn from_captures(captures: &[(usize, &'s str)]) -> Result<Self, ParseError> {
+                if captures.len() != $n {
+                    return Err(ParseError::new(
+                        0,
+                        format!("pattern has {} captures, expected {}", captures.len(), $n),
+                    ));
+                }
+
+                let mut captures = captures.iter();
+                Ok(($({
+                    let (column, s) = captures.next().unwrap();
+                    $t::from_capture(s).map_err(|message| ParseError::new(*column, message))?
+                },)+))
+            }
+        }
+    };
+}
+impl_from_captures!(1; A);
+impl_from_captures!(2; A, B);
+impl_from_captures!(3; A, B, C);
+impl_from_captures!(4; A, B, C, D);
+impl_from_captures!(5; A, B, C, D, E);
+impl_from_captures!(6; A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ints() {
+        let s = "p=0,4 v=3,-3 x-12";
+        assert_eq!(signed_ints::<i32>(s), Ok(vec![0, 4, 3, -3, -12]));
+        assert_eq!(unsigned_ints::<u32>(s), Ok(vec![0, 4, 3, 3, 12]));
+        assert_eq!(signed_ints::<i64>(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_ints_errors() {
+        let e = unsigned_ints::<u8>("1,2,300").unwrap_err();
+        assert_eq!((e.line, e.column), (None, 4));
+        assert!(e.message.starts_with("can't parse \"300\" as u8"));
+
+        let e = signed_ints::<i8>("1 -2\n3 -129 4").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), 2));
+        assert!(e.to_string().starts_with("line 2, column 2: "));
+
+        let e = unsigned_ints::<u64>("99999999999999999999").unwrap_err();
+        assert_eq!(e.column, 0);
+    }
+
+    #[test]
+    fn test_sections() {
+        let input = "a\nb\n\nc\r\n\r\n\n\nd\n";
+        assert_eq!(sections(input).collect::<Vec<_>>(), vec!["a\nb", "c", "d"]);
+        assert_eq!(sections("").count(), 0);
+    }
+
+    #[test]
+    fn test_key_value() {
+        assert_eq!(key_value("Register A: 729"), Some(("Register A", "729")));
+        assert_eq!(key_value("nope"), None);
+    }
+
+    #[test]
+    fn test_pattern() {
+        const ROBOT: Pattern = Pattern::new("p={},{} v={},{}");
+        assert_eq!(ROBOT.parse("p=0,4 v=3,-3"), Ok((0, 4, 3, -3)));
+
+        const GATE: Pattern = Pattern::new("{} {} {} -> {}");
+        assert_eq!(
+            GATE.parse("x00 AND y00 -> z00"),
+            Ok(("x00", "AND", "y00", "z00"))
+        );
+
+        const TRAILING: Pattern = Pattern::new("{}: {}");
+        assert_eq!(
+            TRAILING.parse::<(String, u64)>("Register A: 729"),
+            Ok(("Register A".to_string(), 729))
+        );
+    }
+
+    #[test]
+    fn test_pattern_errors() {
+        const ROBOT: Pattern = Pattern::new("p={},{} v={},{}");
+
+        let e = ROBOT
+            .parse::<(i32, i32, i32, i32)>("q=0,4 v=3,-3")
+            .unwrap_err();
+        assert_eq!(e.column, 0);
+
+        let e = ROBOT
+            .parse::<(i32, i32, i32, i32)>("p=0,4 v=x,-3")
+            .unwrap_err();
+        assert_eq!(e.column, 8);
+
+        let e = ROBOT
+            .parse::<(i32, i32, i32, i32)>("p=0,4 w=3,-3")
+            .unwrap_err()
+            .at_line(3);
+        assert_eq!(e.column, 4);
+        assert_eq!(e.line, Some(3));
+        assert!(e.to_string().starts_with("line 3, column 4: "));
+
+        let e = ROBOT.parse::<(i32, i32)>("p=0,4 v=3,-3").unwrap_err();
+        assert_eq!(e.column, 0);
+
+        const CLOSED: Pattern = Pattern::new("({})");
+        assert_eq!(CLOSED.parse::<(u32,)>("(1)x").unwrap_err().column, 3);
+        assert_eq!(CLOSED.parse::<(u32,)>("()").unwrap_err().column, 1);
+    }
+}
@@ -1,75 +1,442 @@
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    ops::{Add, AddAssign, Div, Mul, Neg, Rem, Sub, SubAssign},
+};
+
+use super::math::gcd;
+
+/// Integer types a `Position` can be made of.
+/// `i32` is the default since most AoC grids are small, `i64`/`i128` are there for the puzzles that aren't.
+pub trait Coord:
+    Copy
+    + Eq
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + From<i32>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+{
+    type Unsigned: Copy + Ord + Debug + Add<Output = Self::Unsigned> + Into<u128>;
+
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn abs(self) -> Self;
+    fn abs_diff(self, other: Self) -> Self::Unsigned;
+    fn rem_euclid(self, rhs: Self) -> Self;
+    fn signum(self) -> Self;
+}
+
+macro_rules! impl_coord {
+    ($t:ty, $u:ty) => {
+        impl Coord for $t {
+            type Unsigned = $u;
+
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+            fn abs_diff(self, other: Self) -> Self::Unsigned {
+                <$t>::abs_diff(self, other)
+            }
+            fn rem_euclid(self, rhs: Self) -> Self {
+                <$t>::rem_euclid(self, rhs)
+            }
+            fn signum(self) -> Self {
+                <$t>::signum(self)
+            }
+        }
+    };
+}
+impl_coord!(i32, u32);
+impl_coord!(i64, u64);
+impl_coord!(i128, u128);
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Position {
-    pub row: i32,
-    pub col: i32,
+pub struct Position<T = i32> {
+    pub row: T,
+    pub col: T,
 }
 
-impl std::fmt::Debug for Position {
+impl<T: Coord> std::fmt::Debug for Position<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({},{})", self.row, self.col)
     }
 }
 
-impl Position {
-    pub fn new(row: i32, col: i32) -> Self {
+impl<T: Coord> Position<T> {
+    pub fn new(row: T, col: T) -> Self {
         Position { row, col }
     }
 
-    pub fn moved_in(self, direction: Direction) -> Self {
+    pub fn moved_in(self, direction: impl Step) -> Self {
         let (d_row, d_col) = direction.to_row_col_diff();
         Position {
-            row: self.row + d_row,
-            col: self.col + d_col,
+            row: self.row + d_row.into(),
+            col: self.col + d_col.into(),
         }
     }
 
+    /// The four orthogonal neighbours.
+    pub fn neighbours(self) -> impl Iterator<Item = Self> {
+        Direction::ALL.into_iter().map(move |d| self.moved_in(d))
+    }
+
     /// Manhattan dist
-    pub fn distance_to(&self, other: &Position) -> u32 {
+    pub fn distance_to(&self, other: &Self) -> T::Unsigned {
         self.row.abs_diff(other.row) + self.col.abs_diff(other.col)
     }
+
+    /// Number of king moves, i.e. diagonal steps count as 1.
+    pub fn chebyshev_distance_to(&self, other: &Self) -> T::Unsigned {
+        self.row
+            .abs_diff(other.row)
+            .max(self.col.abs_diff(other.col))
+    }
+
+    /// Squared so it stays an integer.
+    pub fn squared_euclidean_distance_to(&self, other: &Self) -> u128 {
+        let d_row: u128 = self.row.abs_diff(other.row).into();
+        let d_col: u128 = self.col.abs_diff(other.col).into();
+        d_row * d_row + d_col * d_col
+    }
+
+    /// Smallest step in the same direction with integer components, e.g. `(4,-6)` -> `(2,-3)`.
+    /// Zero stays zero.
+    pub fn reduced(self) -> Self {
+        let g = gcd(self.row, self.col);
+        if g == T::ZERO {
+            self
+        } else {
+            self / g
+        }
+    }
+
+    /// Every lattice point on the segment from `self` to `other`, both ends included.
+    pub fn lattice_points_to(self, other: Self) -> impl Iterator<Item = Self> {
+        let step = (other - self).reduced();
+        std::iter::successors(Some(self), move |p| (*p != other).then(|| p + step))
+    }
+
+    /// Every lattice point on the infinite line through `self` and `other` that lies
+    /// within the inclusive `(min, max)` box, in order along the line.
+    ///
+    /// `self` must be inside the box and differ from `other`.
+    pub fn line_within(self, other: Self, (min, max): (Self, Self)) -> impl Iterator<Item = Self> {
+        let step = (other - self).reduced();
+        assert!(
+            step != Position::new(T::ZERO, T::ZERO),
+            "line needs two distinct points"
+        );
+
+        let in_bounds = move |p: &Self| {
+            (min.row..=max.row).contains(&p.row) && (min.col..=max.col).contains(&p.col)
+        };
+
+        let mut first = self;
+        while in_bounds(&(first - step)) {
+            first -= step;
+        }
+
+        std::iter::successors(Some(first), move |p| Some(p + step)).take_while(in_bounds)
+    }
+
+    /// Rasterized segment from `self` to `other`, both ends included.
+    /// Unlike `lattice_points_to` this visits a cell for every step along the longer axis.
+    pub fn bresenham_to(self, other: Self) -> Bresenham<T> {
+        let d_row = (other.row - self.row).abs();
+        let d_col = (other.col - self.col).abs();
+        Bresenham {
+            next: Some(self),
+            end: other,
+            d_row: -d_row,
+            d_col,
+            s_row: (other.row - self.row).signum(),
+            s_col: (other.col - self.col).signum(),
+            error: d_col - d_row,
+        }
+    }
+}
+
+/// See `Position::bresenham_to`.
+pub struct Bresenham<T = i32> {
+    next: Option<Position<T>>,
+    end: Position<T>,
+    d_row: T,
+    d_col: T,
+    s_row: T,
+    s_col: T,
+    error: T,
+}
+
+impl<T: Coord> Iterator for Bresenham<T> {
+    type Item = Position<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+
+        if current == self.end {
+            self.next = None;
+            return Some(current);
+        }
+
+        let mut next = current;
+        let e2 = self.error + self.error;
+        if e2 >= self.d_row {
+            self.error += self.d_row;
+            next.col += self.s_col;
+        }
+        if e2 <= self.d_col {
+            self.error += self.d_col;
+            next.row += self.s_row;
+        }
+        self.next = Some(next);
+
+        Some(current)
+    }
+}
+
+/// Like `Position` but for the occasional puzzle in 3D.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position3<T = i32> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T: Coord> std::fmt::Debug for Position3<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{},{})", self.x, self.y, self.z)
+    }
+}
+
+impl<T: Coord> Position3<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Position3 { x, y, z }
+    }
+
+    /// The six face-sharing neighbours.
+    pub fn neighbours(self) -> impl Iterator<Item = Self> {
+        let (zero, one) = (T::ZERO, T::ONE);
+        [
+            (one, zero, zero),
+            (-one, zero, zero),
+            (zero, one, zero),
+            (zero, -one, zero),
+            (zero, zero, one),
+            (zero, zero, -one),
+        ]
+        .into_iter()
+        .map(move |(x, y, z)| self + Position3::new(x, y, z))
+    }
+
+    /// All 26 neighbours, including edge and corner ones.
+    pub fn all_neighbours(self) -> impl Iterator<Item = Self> {
+        let range = [-T::ONE, T::ZERO, T::ONE];
+        range
+            .into_iter()
+            .flat_map(move |x| range.into_iter().map(move |y| (x, y)))
+            .flat_map(move |(x, y)| range.into_iter().map(move |z| Position3::new(x, y, z)))
+            .filter(|d| *d != Position3::new(T::ZERO, T::ZERO, T::ZERO))
+            .map(move |d| self + d)
+    }
+
+    /// Manhattan dist
+    pub fn distance_to(&self, other: &Self) -> T::Unsigned {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y) + self.z.abs_diff(other.z)
+    }
+
+    pub fn chebyshev_distance_to(&self, other: &Self) -> T::Unsigned {
+        self.x
+            .abs_diff(other.x)
+            .max(self.y.abs_diff(other.y))
+            .max(self.z.abs_diff(other.z))
+    }
+
+    pub fn squared_euclidean_distance_to(&self, other: &Self) -> u128 {
+        [
+            self.x.abs_diff(other.x),
+            self.y.abs_diff(other.y),
+            self.z.abs_diff(other.z),
+        ]
+        .into_iter()
+        .map(|d| {
+            let d: u128 = d.into();
+            d * d
+        })
+        .sum()
+    }
 }
 
 macro_rules! impl_position_ops {
-    ($lhs:ty, $rhs:ty) => {
-        impl std::ops::Add<$rhs> for $lhs {
-            type Output = Position;
+    ($name:ident, $lhs:ty, $rhs:ty, $($field:ident),+) => {
+        impl<T: Coord> std::ops::Add<$rhs> for $lhs {
+            type Output = $name<T>;
 
             fn add(self, rhs: $rhs) -> Self::Output {
-                Position::new(self.row + rhs.row, self.col + rhs.col)
+                $name { $($field: self.$field + rhs.$field),+ }
             }
         }
-        impl std::ops::Sub<$rhs> for $lhs {
-            type Output = Position;
+        impl<T: Coord> std::ops::Sub<$rhs> for $lhs {
+            type Output = $name<T>;
 
             fn sub(self, rhs: $rhs) -> Self::Output {
-                Position::new(self.row - rhs.row, self.col - rhs.col)
+                $name { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+        // Euclidean remainder per axis, so wrapping around a grid of size `rhs` never goes negative.
+        impl<T: Coord> std::ops::Rem<$rhs> for $lhs {
+            type Output = $name<T>;
+
+            fn rem(self, rhs: $rhs) -> Self::Output {
+                $name { $($field: self.$field.rem_euclid(rhs.$field)),+ }
             }
         }
     };
 }
-impl_position_ops!(Position, Position);
-impl_position_ops!(Position, &Position);
-impl_position_ops!(&Position, Position);
-impl_position_ops!(&Position, &Position);
+impl_position_ops!(Position, Position<T>, Position<T>, row, col);
+impl_position_ops!(Position, Position<T>, &Position<T>, row, col);
+impl_position_ops!(Position, &Position<T>, Position<T>, row, col);
+impl_position_ops!(Position, &Position<T>, &Position<T>, row, col);
+impl_position_ops!(Position3, Position3<T>, Position3<T>, x, y, z);
+impl_position_ops!(Position3, Position3<T>, &Position3<T>, x, y, z);
+impl_position_ops!(Position3, &Position3<T>, Position3<T>, x, y, z);
+impl_position_ops!(Position3, &Position3<T>, &Position3<T>, x, y, z);
 
 macro_rules! impl_position_assign_ops {
-    ($rhs:ty) => {
-        impl std::ops::AddAssign<$rhs> for Position {
+    ($name:ident, $rhs:ty, $($field:ident),+) => {
+        impl<T: Coord> std::ops::AddAssign<$rhs> for $name<T> {
             fn add_assign(&mut self, rhs: $rhs) {
-                (*self).row += rhs.row;
-                (*self).col += rhs.col;
+                $(self.$field += rhs.$field;)+
             }
         }
-        impl std::ops::SubAssign<$rhs> for Position {
+        impl<T: Coord> std::ops::SubAssign<$rhs> for $name<T> {
             fn sub_assign(&mut self, rhs: $rhs) {
-                (*self).row += rhs.row;
-                (*self).col += rhs.col;
+                $(self.$field -= rhs.$field;)+
+            }
+        }
+        impl<T: Coord> std::ops::RemAssign<$rhs> for $name<T> {
+            fn rem_assign(&mut self, rhs: $rhs) {
+                *self = *self % rhs;
+            }
+        }
+    };
+}
+impl_position_assign_ops!(Position, Position<T>, row, col);
+impl_position_assign_ops!(Position, &Position<T>, row, col);
+impl_position_assign_ops!(Position3, Position3<T>, x, y, z);
+impl_position_assign_ops!(Position3, &Position3<T>, x, y, z);
+
+macro_rules! impl_position_scalar_ops {
+    ($name:ident, $lhs:ty, $($field:ident),+) => {
+        impl<T: Coord> std::ops::Neg for $lhs {
+            type Output = $name<T>;
+
+            fn neg(self) -> Self::Output {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+        impl<T: Coord> std::ops::Mul<T> for $lhs {
+            type Output = $name<T>;
+
+            fn mul(self, rhs: T) -> Self::Output {
+                $name { $($field: self.$field * rhs),+ }
+            }
+        }
+        // Truncating division, same as the underlying integer.
+        impl<T: Coord> std::ops::Div<T> for $lhs {
+            type Output = $name<T>;
+
+            fn div(self, rhs: T) -> Self::Output {
+                $name { $($field: self.$field / rhs),+ }
+            }
+        }
+    };
+}
+impl_position_scalar_ops!(Position, Position<T>, row, col);
+impl_position_scalar_ops!(Position, &Position<T>, row, col);
+impl_position_scalar_ops!(Position3, Position3<T>, x, y, z);
+impl_position_scalar_ops!(Position3, &Position3<T>, x, y, z);
+
+macro_rules! impl_position_scalar_assign_ops {
+    ($name:ident) => {
+        impl<T: Coord> std::ops::MulAssign<T> for $name<T> {
+            fn mul_assign(&mut self, rhs: T) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl<T: Coord> std::ops::DivAssign<T> for $name<T> {
+            fn div_assign(&mut self, rhs: T) {
+                *self = *self / rhs;
+            }
+        }
+    };
+}
+impl_position_scalar_assign_ops!(Position);
+impl_position_scalar_assign_ops!(Position3);
+
+impl<T> From<(T, T)> for Position<T> {
+    fn from((row, col): (T, T)) -> Self {
+        Position { row, col }
+    }
+}
+
+impl<T> From<Position<T>> for (T, T) {
+    fn from(value: Position<T>) -> Self {
+        (value.row, value.col)
+    }
+}
+
+impl<T> From<(T, T, T)> for Position3<T> {
+    fn from((x, y, z): (T, T, T)) -> Self {
+        Position3 { x, y, z }
+    }
+}
+
+impl<T> From<Position3<T>> for (T, T, T) {
+    fn from(value: Position3<T>) -> Self {
+        (value.x, value.y, value.z)
+    }
+}
+
+macro_rules! impl_position_usize_conversions {
+    ($t:ty) => {
+        impl From<(usize, usize)> for Position<$t> {
+            fn from((row, col): (usize, usize)) -> Self {
+                Position::new(row as $t, col as $t)
+            }
+        }
+
+        // Fails for positions off the top or left of a grid.
+        impl TryFrom<Position<$t>> for (usize, usize) {
+            type Error = std::num::TryFromIntError;
+
+            fn try_from(value: Position<$t>) -> Result<Self, Self::Error> {
+                Ok((value.row.try_into()?, value.col.try_into()?))
             }
         }
     };
 }
-impl_position_assign_ops!(Position);
-impl_position_assign_ops!(&Position);
+impl_position_usize_conversions!(i32);
+impl_position_usize_conversions!(i64);
+
+impl From<Position<i32>> for Position<i64> {
+    fn from(value: Position<i32>) -> Self {
+        Position::new(value.row.into(), value.col.into())
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
@@ -99,12 +466,25 @@ impl Direction {
         }
     }
 
-    fn to_row_col_diff(self) -> (i32, i32) {
+    /// Accepts both `U/D/L/R` and compass `N/S/W/E`.
+    #[allow(dead_code)]
+    pub fn from_letter(c: char) -> Option<Self> {
+        Some(match c {
+            'U' | 'N' => Direction::Up,
+            'R' | 'E' => Direction::Right,
+            'D' | 'S' => Direction::Down,
+            'L' | 'W' => Direction::Left,
+            _ => return None,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn to_letter(self) -> char {
         match self {
-            Direction::Down => (1, 0),
-            Direction::Left => (0, -1),
-            Direction::Up => (-1, 0),
-            Direction::Right => (0, 1),
+            Direction::Up => 'U',
+            Direction::Right => 'R',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
         }
     }
 
@@ -118,6 +498,10 @@ impl Direction {
         }
     }
 
+    pub fn rotated_anticlockwise(self) -> Self {
+        self.rotated_clockwise().reversed()
+    }
+
     pub fn reversed(self) -> Self {
         match self {
             Direction::Down => Direction::Up,
@@ -134,3 +518,504 @@ impl Direction {
         Direction::Right,
     ];
 }
+
+/// Anything `Position::moved_in` can take a single step in.
+pub trait Step: Copy {
+    fn to_row_col_diff(self) -> (i32, i32);
+}
+
+impl Step for Direction {
+    fn to_row_col_diff(self) -> (i32, i32) {
+        match self {
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Up => (-1, 0),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// All eight directions, including diagonals.
+/// `N` is up the grid (decreasing row), same as `Direction::Up`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Compass {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Compass {
+    /// Clockwise from `N`.
+    pub const ALL8: [Compass; 8] = [
+        Compass::N,
+        Compass::NE,
+        Compass::E,
+        Compass::SE,
+        Compass::S,
+        Compass::SW,
+        Compass::W,
+        Compass::NW,
+    ];
+
+    #[allow(dead_code)]
+    pub const ALL_DIAGONAL: [Compass; 4] = [Compass::NE, Compass::SE, Compass::SW, Compass::NW];
+
+    #[allow(dead_code)]
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    #[allow(dead_code)]
+    fn from_index(i: usize) -> Self {
+        Compass::ALL8[i % 8]
+    }
+
+    #[allow(dead_code)]
+    pub fn is_diagonal(self) -> bool {
+        self.index() % 2 == 1
+    }
+
+    #[allow(dead_code)]
+    pub fn rotated_clockwise_45(self) -> Self {
+        Compass::from_index(self.index() + 1)
+    }
+
+    #[allow(dead_code)]
+    pub fn rotated_anticlockwise_45(self) -> Self {
+        Compass::from_index(self.index() + 7)
+    }
+
+    #[allow(dead_code)]
+    pub fn rotated_clockwise(self) -> Self {
+        Compass::from_index(self.index() + 2)
+    }
+
+    #[allow(dead_code)]
+    pub fn rotated_anticlockwise(self) -> Self {
+        Compass::from_index(self.index() + 6)
+    }
+
+    #[allow(dead_code)]
+    pub fn reversed(self) -> Self {
+        Compass::from_index(self.index() + 4)
+    }
+
+    /// Accepts compass letters (`N`, `NE`, ...) and screen letters (`U`, `UR`, ...).
+    #[allow(dead_code)]
+    pub fn from_letters(s: &str) -> Option<Self> {
+        Some(match s {
+            "N" | "U" => Compass::N,
+            "NE" | "UR" => Compass::NE,
+            "E" | "R" => Compass::E,
+            "SE" | "DR" => Compass::SE,
+            "S" | "D" => Compass::S,
+            "SW" | "DL" => Compass::SW,
+            "W" | "L" => Compass::W,
+            "NW" | "UL" => Compass::NW,
+            _ => return None,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn to_letters(self) -> &'static str {
+        match self {
+            Compass::N => "N",
+            Compass::NE => "NE",
+            Compass::E => "E",
+            Compass::SE => "SE",
+            Compass::S => "S",
+            Compass::SW => "SW",
+            Compass::W => "W",
+            Compass::NW => "NW",
+        }
+    }
+
+    /// Only the four orthogonal directions have arrows.
+    #[allow(dead_code)]
+    pub fn from_arrow(c: char) -> Option<Self> {
+        Direction::from_arrow(c).map(Compass::from)
+    }
+}
+
+impl Step for Compass {
+    fn to_row_col_diff(self) -> (i32, i32) {
+        match self {
+            Compass::N => (-1, 0),
+            Compass::NE => (-1, 1),
+            Compass::E => (0, 1),
+            Compass::SE => (1, 1),
+            Compass::S => (1, 0),
+            Compass::SW => (1, -1),
+            Compass::W => (0, -1),
+            Compass::NW => (-1, -1),
+        }
+    }
+}
+
+impl From<Direction> for Compass {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::Up => Compass::N,
+            Direction::Right => Compass::E,
+            Direction::Down => Compass::S,
+            Direction::Left => Compass::W,
+        }
+    }
+}
+
+impl TryFrom<Compass> for Direction {
+    type Error = ();
+
+    fn try_from(value: Compass) -> Result<Self, Self::Error> {
+        Ok(match value {
+            Compass::N => Direction::Up,
+            Compass::E => Direction::Right,
+            Compass::S => Direction::Down,
+            Compass::W => Direction::Left,
+            _ => return Err(()),
+        })
+    }
+}
+
+// A direction scaled by `n` is the offset of moving `n` steps that way.
+macro_rules! impl_step_mul {
+    ($t:ty) => {
+        impl std::ops::Mul<i32> for $t {
+            type Output = Position;
+
+            fn mul(self, rhs: i32) -> Self::Output {
+                let (d_row, d_col) = self.to_row_col_diff();
+                Position::new(d_row * rhs, d_col * rhs)
+            }
+        }
+    };
+}
+impl_step_mul!(Direction);
+impl_step_mul!(Compass);
+
+#[cfg(test)]
+// Deliberately exercising the reference impls.
+#[allow(clippy::op_ref)]
+mod tests {
+    use super::*;
+
+    // Small enough to exhaustively check every pair, big enough to cover signs and zero.
+    fn positions() -> impl Iterator<Item = Position> + Clone {
+        (-4..=4).flat_map(|row| (-4..=4).map(move |col| Position::new(row, col)))
+    }
+
+    #[test]
+    fn test_add_sub() {
+        for a in positions() {
+            for b in positions() {
+                let sum = a + b;
+                assert_eq!(sum, Position::new(a.row + b.row, a.col + b.col));
+                assert_eq!(sum, &a + &b);
+                assert_eq!(sum, a + &b);
+                assert_eq!(sum, &a + b);
+                assert_eq!(sum - b, a);
+                assert_eq!(a - b, a + -b);
+                assert_eq!(a - b, -(b - a));
+
+                let mut x = a;
+                x += b;
+                assert_eq!(x, sum);
+                x -= b;
+                assert_eq!(x, a);
+                x -= &b;
+                assert_eq!(x, a - b);
+                x += &b;
+                assert_eq!(x, a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_neg() {
+        for a in positions() {
+            assert_eq!(-a, Position::new(-a.row, -a.col));
+            assert_eq!(-(-a), a);
+            assert_eq!(-&a, -a);
+            assert_eq!(a + -a, Position::new(0, 0));
+        }
+    }
+
+    #[test]
+    fn test_mul_div() {
+        for a in positions() {
+            for n in -4..=4 {
+                let scaled = a * n;
+                assert_eq!(scaled, Position::new(a.row * n, a.col * n));
+                assert_eq!(&a * n, scaled);
+
+                let mut x = a;
+                x *= n;
+                assert_eq!(x, scaled);
+
+                if n != 0 {
+                    assert_eq!(scaled / n, a);
+                    assert_eq!(&scaled / n, a);
+                    assert_eq!(a / n, Position::new(a.row / n, a.col / n));
+
+                    x /= n;
+                    assert_eq!(x, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rem() {
+        for a in positions() {
+            for b in positions().filter(|b| b.row > 0 && b.col > 0) {
+                let r = a % b;
+                assert!((0..b.row).contains(&r.row));
+                assert!((0..b.col).contains(&r.col));
+                assert_eq!((r.row - a.row) % b.row, 0);
+                assert_eq!((r.col - a.col) % b.col, 0);
+                assert_eq!(&a % &b, r);
+                assert_eq!((a + b * 3) % b, r);
+
+                let mut x = a;
+                x %= b;
+                assert_eq!(x, r);
+            }
+        }
+    }
+
+    #[test]
+    fn test_direction_mul() {
+        for d in Direction::ALL {
+            assert_eq!(d * 0, Position::new(0, 0));
+            assert_eq!(d * 1, Position::new(0, 0).moved_in(d));
+            assert_eq!(d * -1, d.reversed() * 1);
+            let mut p = Position::new(0, 0);
+            for n in 0..=4 {
+                assert_eq!(d * n, p);
+                p = p.moved_in(d);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compass_rotation() {
+        for c in Compass::ALL8 {
+            assert_eq!(c.rotated_clockwise_45().rotated_anticlockwise_45(), c);
+            assert_eq!(
+                c.rotated_clockwise_45().rotated_clockwise_45(),
+                c.rotated_clockwise()
+            );
+            assert_eq!(c.rotated_clockwise().rotated_clockwise(), c.reversed());
+            assert_eq!(c.rotated_anticlockwise().rotated_clockwise(), c);
+            assert_eq!(c.reversed() * 1, -(c * 1));
+            assert_ne!(c.is_diagonal(), c.rotated_clockwise_45().is_diagonal());
+
+            // Rotating the offset a quarter turn clockwise is (row, col) -> (col, -row).
+            let (d_row, d_col) = c.to_row_col_diff();
+            assert_eq!(c.rotated_clockwise() * 1, Position::new(d_col, -d_row));
+        }
+
+        assert!(Compass::ALL_DIAGONAL.iter().all(|c| c.is_diagonal()));
+    }
+
+    #[test]
+    fn test_compass_direction_agree() {
+        for d in Direction::ALL {
+            let c = Compass::from(d);
+            assert_eq!(c * 1, d * 1);
+            assert_eq!(Direction::try_from(c), Ok(d));
+            assert_eq!(Compass::from(d.rotated_clockwise()), c.rotated_clockwise());
+            assert_eq!(
+                Compass::from(d.rotated_anticlockwise()),
+                c.rotated_anticlockwise()
+            );
+            assert_eq!(Compass::from_arrow(d.to_arrow()), Some(c));
+            assert_eq!(Direction::from_letter(d.to_letter()), Some(d));
+            assert_eq!(Compass::from_letters(&d.to_letter().to_string()), Some(c));
+        }
+
+        for c in Compass::ALL_DIAGONAL {
+            assert!(Direction::try_from(c).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compass_letters() {
+        for c in Compass::ALL8 {
+            assert_eq!(Compass::from_letters(c.to_letters()), Some(c));
+        }
+        assert_eq!(Compass::from_letters("UL"), Some(Compass::NW));
+        assert_eq!(Compass::from_letters("X"), None);
+        assert_eq!(Direction::from_letter('N'), Some(Direction::Up));
+    }
+
+    #[test]
+    fn test_distances() {
+        for a in positions() {
+            for b in positions() {
+                let d = b - a;
+                let (r, c) = (d.row.unsigned_abs(), d.col.unsigned_abs());
+
+                assert_eq!(a.distance_to(&b), r + c);
+                assert_eq!(a.chebyshev_distance_to(&b), r.max(c));
+                assert_eq!(a.squared_euclidean_distance_to(&b), (r * r + c * c) as u128);
+
+                assert_eq!(a.distance_to(&b), b.distance_to(&a));
+                assert_eq!(a.chebyshev_distance_to(&b), b.chebyshev_distance_to(&a));
+                assert!(a.chebyshev_distance_to(&b) <= a.distance_to(&b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_reduced() {
+        assert_eq!(Position::new(4, -6).reduced(), Position::new(2, -3));
+        assert_eq!(Position::new(0, -5).reduced(), Position::new(0, -1));
+        assert_eq!(Position::new(0, 0).reduced(), Position::new(0, 0));
+
+        for a in positions().filter(|a| *a != Position::new(0, 0)) {
+            let r = a.reduced();
+            // Same direction, and a whole number of steps.
+            assert_eq!(r.row * a.col, r.col * a.row);
+            assert!(r.row.signum() == a.row.signum() && r.col.signum() == a.col.signum());
+            assert_eq!(r.reduced(), r);
+        }
+    }
+
+    #[test]
+    fn test_lattice_lines() {
+        let a = Position::new(1, 1);
+        let b = Position::new(3, 5);
+        assert_eq!(
+            a.lattice_points_to(b).collect::<Vec<_>>(),
+            vec![a, Position::new(2, 3), b]
+        );
+
+        let bounds = (Position::new(0, 0), Position::new(5, 7));
+        assert_eq!(
+            a.line_within(b, bounds).collect::<Vec<_>>(),
+            vec![a, Position::new(2, 3), b, Position::new(4, 7)]
+        );
+
+        // Brute force: every in-bounds point collinear with a and b.
+        for a in positions() {
+            for b in positions().filter(|b| *b != a) {
+                let bounds = (Position::new(-4, -4), Position::new(4, 4));
+                let line = a.line_within(b, bounds).collect::<Vec<_>>();
+                let expected = positions()
+                    .filter(|p| {
+                        let (u, v) = (*p - a, b - a);
+                        u.row * v.col == u.col * v.row
+                    })
+                    .count();
+                assert_eq!(line.len(), expected);
+                assert!(line.contains(&a) && line.contains(&b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_bresenham() {
+        assert_eq!(
+            Position::new(0, 0)
+                .bresenham_to(Position::new(1, 3))
+                .collect::<Vec<_>>(),
+            vec![
+                Position::new(0, 0),
+                Position::new(0, 1),
+                Position::new(1, 2),
+                Position::new(1, 3),
+            ]
+        );
+
+        for a in positions() {
+            for b in positions() {
+                let line = a.bresenham_to(b).collect::<Vec<_>>();
+                assert_eq!(line.len() as u32, a.chebyshev_distance_to(&b) + 1);
+                assert_eq!(line.first(), Some(&a));
+                assert_eq!(line.last(), Some(&b));
+                assert!(line
+                    .windows(2)
+                    .all(|w| w[0].chebyshev_distance_to(&w[1]) == 1));
+                // Exact lattice points are always hit.
+                assert!(a.lattice_points_to(b).all(|p| line.contains(&p)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_i64() {
+        let big = 10_000_000_000_000i64;
+        let a: Position<i64> = Position::new(big, -big);
+        let b: Position<i64> = Position::new(3, 4).into();
+
+        assert_eq!(a + b, Position::new(big + 3, 4 - big));
+        assert_eq!(a * 2, Position::new(2 * big, -2 * big));
+        assert_eq!(
+            a % Position::new(7, 7),
+            Position::new(big % 7, (-big).rem_euclid(7))
+        );
+        assert_eq!(a.distance_to(&b), (big - 3) as u64 + (big + 4) as u64);
+        assert_eq!(a.moved_in(Direction::Up), Position::new(big - 1, -big));
+        assert_eq!(
+            Position::new(big * 4, big * 6).reduced(),
+            Position::new(2, 3)
+        );
+    }
+
+    #[test]
+    fn test_position3() {
+        let origin: Position3 = Position3::new(0, 0, 0);
+        let a = Position3::new(1, -2, 3);
+        let b = Position3::new(-4, 5, 0);
+
+        assert_eq!(a + b, Position3::new(-3, 3, 3));
+        assert_eq!(a - b, Position3::new(5, -7, 3));
+        assert_eq!(-a, Position3::new(-1, 2, -3));
+        assert_eq!(a * 3, Position3::new(3, -6, 9));
+        assert_eq!((a * 3) / 3, a);
+        assert_eq!(a % Position3::new(2, 2, 2), Position3::new(1, 0, 1));
+
+        let mut x = a;
+        x += b;
+        x -= &b;
+        assert_eq!(x, a);
+
+        assert_eq!(a.distance_to(&b), 5 + 7 + 3);
+        assert_eq!(a.chebyshev_distance_to(&b), 7);
+        assert_eq!(a.squared_euclidean_distance_to(&b), 25 + 49 + 9);
+
+        assert_eq!(origin.neighbours().count(), 6);
+        assert!(origin.neighbours().all(|n| n.distance_to(&origin) == 1));
+        assert_eq!(origin.all_neighbours().count(), 26);
+        assert!(origin
+            .all_neighbours()
+            .all(|n| n.chebyshev_distance_to(&origin) == 1));
+
+        let t: (i32, i32, i32) = a.into();
+        assert_eq!(Position3::from(t), a);
+    }
+
+    #[test]
+    fn test_conversions() {
+        for a in positions() {
+            let t: (i32, i32) = a.into();
+            assert_eq!(t, (a.row, a.col));
+            assert_eq!(Position::from(t), a);
+
+            let u: Result<(usize, usize), _> = a.try_into();
+            if a.row >= 0 && a.col >= 0 {
+                let u = u.unwrap();
+                assert_eq!(u, (a.row as usize, a.col as usize));
+                assert_eq!(Position::from(u), a);
+            } else {
+                assert!(u.is_err());
+            }
+        }
+    }
+}
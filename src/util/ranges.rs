@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, ops::Range};
+
+/// A set of integers stored as disjoint half-open ranges.
+/// Touching or overlapping ranges are merged on insert, so each stored range is maximal.
+#[derive(Clone, Debug, Default)]
+pub struct RangeSet {
+    /// start -> end
+    ranges: BTreeMap<i64, i64>,
+    /// The same ranges again, indexed for `first_fit`.
+    fits: FitTree,
+    total: i64,
+}
+
+impl PartialEq for RangeSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.ranges == other.ranges
+    }
+}
+
+impl Eq for RangeSet {}
+
+impl RangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of disjoint ranges.
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Number of integers in the set.
+    #[allow(dead_code)]
+    pub fn total_length(&self) -> i64 {
+        self.total
+    }
+
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = Range<i64>> + '_ {
+        self.ranges.iter().map(|(start, end)| *start..*end)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, x: i64) -> bool {
+        self.range_containing(x).is_some()
+    }
+
+    /// The stored range `x` is in.
+    #[allow(dead_code)]
+    pub fn range_containing(&self, x: i64) -> Option<Range<i64>> {
+        let (start, end) = self.ranges.range(..=x).next_back()?;
+        (x < *end).then_some(*start..*end)
+    }
+
+    pub fn insert(&mut self, range: Range<i64>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut start, mut end) = (range.start, range.end);
+
+        // Absorb anything overlapping or touching, starting with a range that begins before us.
+        if let Some((s, e)) = self.ranges.range(..=start).next_back() {
+            if *e >= start {
+                (start, end) = (*s, end.max(*e));
+            }
+        }
+        while let Some((s, e)) = self.ranges.range(start..=end).next() {
+            let (s, e) = (*s, *e);
+            self.remove_stored(s, e);
+            end = end.max(e);
+        }
+
+        self.insert_stored(start, end);
+    }
+
+    pub fn remove(&mut self, range: Range<i64>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // Anything that overlaps `range` starts before its end.
+        let overlapping = self
+            .ranges
+            .range(..range.end)
+            .rev()
+            .take_while(|(_, e)| **e > range.start)
+            .map(|(s, e)| (*s, *e))
+            .collect::<Vec<_>>();
+
+        for (s, e) in overlapping {
+            self.remove_stored(s, e);
+            if s < range.start {
+                self.insert_stored(s, range.start);
+            }
+            if e > range.end {
+                self.insert_stored(range.end, e);
+            }
+        }
+    }
+
+    /// Adds everything in `other`.
+    #[allow(dead_code)]
+    pub fn merge(&mut self, other: &RangeSet) {
+        for range in other.iter() {
+            self.insert(range);
+        }
+    }
+
+    /// Splits off everything at or after `at`, splitting a range that crosses it.
+    #[allow(dead_code)]
+    pub fn split_off(&mut self, at: i64) -> RangeSet {
+        let mut other = RangeSet::new();
+
+        let moved = self
+            .ranges
+            .range(..)
+            .rev()
+            .take_while(|(_, e)| **e > at)
+            .map(|(s, e)| (*s, *e))
+            .collect::<Vec<_>>();
+
+        for (s, e) in moved {
+            self.remove_stored(s, e);
+            if s < at {
+                self.insert_stored(s, at);
+            }
+            other.insert_stored(s.max(at), e);
+        }
+
+        other
+    }
+
+    /// Leftmost stored range at least `len` long that starts before `before`,
+    /// e.g. the first gap a file fits in when the set holds free space.
+    /// Logarithmic in the number of ranges, however many different lengths there are.
+    pub fn first_fit(&self, len: i64, before: i64) -> Option<Range<i64>> {
+        // Every stored range is at least 1 long.
+        let start = self
+            .fits
+            .leftmost_fit(len.max(1))
+            .filter(|start| *start < before)?;
+
+        Some(start..self.ranges[&start])
+    }
+
+    fn insert_stored(&mut self, start: i64, end: i64) {
+        self.ranges.insert(start, end);
+        self.fits.insert(start, end - start);
+        self.total += end - start;
+    }
+
+    fn remove_stored(&mut self, start: i64, end: i64) {
+        self.ranges.remove(&start);
+        self.fits.remove(start);
+        self.total -= end - start;
+    }
+}
+
+type Link = Option<Box<FitNode>>;
+
+/// Ranges keyed by start in a treap, where each node also knows the longest range
+/// under it. That's enough to go straight down to the leftmost range that's long enough.
+#[derive(Clone, Debug, Default)]
+struct FitTree {
+    root: Link,
+}
+
+#[derive(Clone, Debug)]
+struct FitNode {
+    start: i64,
+    len: i64,
+    /// Derived from `start`, so the tree's shape only depends on what's in it.
+    priority: u64,
+    max_len: i64,
+    left: Link,
+    right: Link,
+}
+
+impl FitNode {
+    fn update(&mut self) {
+        self.max_len = self.len.max(max_len(&self.left)).max(max_len(&self.right));
+    }
+}
+
+fn max_len(link: &Link) -> i64 {
+    link.as_ref().map_or(0, |n| n.max_len)
+}
+
+// splitmix64, which scatters consecutive starts well.
+fn priority(start: i64) -> u64 {
+    let mut x = (start as u64).wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Splits into the nodes starting before `at` and the rest.
+fn split(link: Link, at: i64) -> (Link, Link) {
+    let Some(mut node) = link else {
+        return (None, None);
+    };
+
+    if node.start < at {
+        let (l, r) = split(node.right.take(), at);
+        node.right = l;
+        node.update();
+        (Some(node), r)
+    } else {
+        let (l, r) = split(node.left.take(), at);
+        node.left = r;
+        node.update();
+        (l, Some(node))
+    }
+}
+
+/// Joins two trees where everything in `a` starts before everything in `b`.
+fn merge(a: Link, b: Link) -> Link {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(mut b)) => {
+            if a.priority > b.priority {
+                a.right = merge(a.right.take(), Some(b));
+                a.update();
+                Some(a)
+            } else {
+                b.left = merge(Some(a), b.left.take());
+                b.update();
+                Some(b)
+            }
+        }
+    }
+}
+
+impl FitTree {
+    fn insert(&mut self, start: i64, len: i64) {
+        let node = Box::new(FitNode {
+            start,
+            len,
+            priority: priority(start),
+            max_len: len,
+            left: None,
+            right: None,
+        });
+        let (l, r) = split(self.root.take(), start);
+        self.root = merge(merge(l, Some(node)), r);
+    }
+
+    fn remove(&mut self, start: i64) {
+        let (l, r) = split(self.root.take(), start);
+        let (_, r) = split(r, start + 1);
+        self.root = merge(l, r);
+    }
+
+    /// Start of the leftmost range at least `len` long, `len` being positive.
+    fn leftmost_fit(&self, len: i64) -> Option<i64> {
+        let mut node = self.root.as_deref().filter(|n| n.max_len >= len)?;
+        loop {
+            // Something on the left fits, so it's further left than this one.
+            if max_len(&node.left) >= len {
+                node = node.left.as_deref().unwrap();
+            } else if node.len >= len {
+                return Some(node.start);
+            } else {
+                node = node.right.as_deref().unwrap();
+            }
+        }
+    }
+}
+
+impl FromIterator<Range<i64>> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = Range<i64>>>(iter: I) -> Self {
+        let mut set = RangeSet::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::Rng;
+
+    // Brute force model of a set of small integers.
+    fn model(set: &RangeSet) -> Vec<bool> {
+        (0..40).map(|x| set.contains(x)).collect()
+    }
+
+    fn check_invariants(set: &RangeSet) {
+        let ranges = set.iter().collect::<Vec<_>>();
+        for w in ranges.windows(2) {
+            // Disjoint and not touching, otherwise they should have been merged.
+            assert!(w[0].end < w[1].start, "{:?}", ranges);
+        }
+        assert!(ranges.iter().all(|r| !r.is_empty()));
+        assert_eq!(
+            set.total_length(),
+            ranges.iter().map(|r| r.end - r.start).sum::<i64>()
+        );
+    }
+
+    #[test]
+    fn test_insert_remove_against_model() {
+        let mut set = RangeSet::new();
+        let mut expected = vec![false; 40];
+
+        let mut rng = Rng::new(7);
+
+        for _ in 0..2000 {
+            let start = rng.below(36) as i64;
+            let end = start + rng.below(6) as i64;
+            if rng.below(3) == 0 {
+                set.remove(start..end);
+                expected[start as usize..end as usize].fill(false);
+            } else {
+                set.insert(start..end);
+                expected[start as usize..end as usize].fill(true);
+            }
+
+            check_invariants(&set);
+            assert_eq!(model(&set), expected);
+        }
+    }
+
+    #[test]
+    fn test_merge_split() {
+        let mut a: RangeSet = [0..3, 10..12].into_iter().collect();
+        let b: RangeSet = [3..5, 11..20].into_iter().collect();
+        a.merge(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![0..5, 10..20]);
+
+        let c = a.split_off(15);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![0..5, 10..15]);
+        assert_eq!(c.iter().collect::<Vec<_>>(), vec![15..20]);
+        check_invariants(&a);
+        check_invariants(&c);
+
+        let d = a.split_off(5);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![0..5]);
+        assert_eq!(d.iter().collect::<Vec<_>>(), vec![10..15]);
+    }
+
+    #[test]
+    fn test_first_fit() {
+        let set: RangeSet = [0..1, 3..6, 8..10, 20..30].into_iter().collect();
+
+        assert_eq!(set.first_fit(1, 100), Some(0..1));
+        assert_eq!(set.first_fit(2, 100), Some(3..6));
+        assert_eq!(set.first_fit(4, 100), Some(20..30));
+        assert_eq!(set.first_fit(4, 20), None);
+        assert_eq!(set.first_fit(11, 100), None);
+
+        assert_eq!(set.range_containing(4), Some(3..6));
+        assert_eq!(set.range_containing(6), None);
+    }
+
+    #[test]
+    fn test_first_fit_against_scan() {
+        // Many different lengths, which is where a per-length index gets slow.
+        let mut set = RangeSet::new();
+        for i in 0..2000 {
+            set.insert(i * 3000..i * 3000 + 1 + (i * 7919) % 2500);
+        }
+
+        for len in (0..2600).step_by(13) {
+            let expected = set.iter().find(|r| r.end - r.start >= len);
+            assert_eq!(set.first_fit(len, i64::MAX), expected);
+        }
+
+        // Carving up the fits keeps the index in step.
+        while let Some(r) = set.first_fit(1000, i64::MAX) {
+            set.remove(r.start..r.start + 1000);
+        }
+        assert!(set.iter().all(|r| r.end - r.start < 1000));
+        check_invariants(&set);
+    }
+}
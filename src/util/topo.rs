@@ -0,0 +1,111 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+/// Nodes that depend on each other in a loop, in edge order.
+/// The last node has an edge back to the first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle<N>(pub Vec<N>);
+
+/// Orders `nodes` so every `(before, after)` edge points forwards, in O(V+E).
+///
+/// Ties are broken by the order of `nodes`, so the result is deterministic.
+/// Edges mentioning nodes not in `nodes` are ignored.
+pub fn topological_sort<N, I>(nodes: &[N], edges: I) -> Result<Vec<N>, Cycle<N>>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = (N, N)>,
+{
+    let index: HashMap<N, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+
+    let mut successors = vec![Vec::new(); nodes.len()];
+    let mut predecessors = vec![Vec::new(); nodes.len()];
+    let mut in_degree = vec![0; nodes.len()];
+    let mut seen = HashSet::new();
+    for (a, b) in edges {
+        let (Some(&a), Some(&b)) = (index.get(&a), index.get(&b)) else {
+            continue;
+        };
+        if !seen.insert((a, b)) {
+            continue;
+        }
+        successors[a].push(b);
+        predecessors[b].push(a);
+        in_degree[b] += 1;
+    }
+
+    let mut q: VecDeque<usize> = (0..nodes.len()).filter(|i| in_degree[*i] == 0).collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(i) = q.pop_front() {
+        order.push(nodes[i]);
+        for &j in &successors[i] {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 {
+                q.push_back(j);
+            }
+        }
+    }
+
+    if order.len() == nodes.len() {
+        return Ok(order);
+    }
+
+    // Everything left still has an unprocessed predecessor,
+    // so walking backwards through those has to loop eventually.
+    let mut i = (0..nodes.len()).find(|i| in_degree[*i] > 0).unwrap();
+    let mut visited_at = HashMap::new();
+    let mut path = Vec::new();
+    while !visited_at.contains_key(&i) {
+        visited_at.insert(i, path.len());
+        path.push(i);
+        i = *predecessors[i].iter().find(|j| in_degree[**j] > 0).unwrap();
+    }
+
+    let mut cycle = path[visited_at[&i]..]
+        .iter()
+        .map(|i| nodes[*i])
+        .collect::<Vec<_>>();
+    // Walked it backwards.
+    cycle.reverse();
+    Err(Cycle(cycle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_valid_order(order: &[u32], edges: &[(u32, u32)]) -> bool {
+        let pos = |n: u32| order.iter().position(|m| *m == n).unwrap();
+        edges.iter().all(|(a, b)| pos(*a) < pos(*b))
+    }
+
+    #[test]
+    fn test_sort() {
+        let edges = [(5, 3), (3, 1), (5, 1), (4, 1), (2, 4)];
+        let order = topological_sort(&[1, 2, 3, 4, 5], edges).unwrap();
+
+        assert_eq!(order.len(), 5);
+        assert!(is_valid_order(&order, &edges));
+        assert_eq!(order, vec![2, 5, 4, 3, 1]);
+
+        // Edges to unknown nodes are ignored.
+        assert_eq!(topological_sort(&[1, 2], [(1, 9), (2, 1)]), Ok(vec![2, 1]));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let edges = [(1, 2), (2, 3), (3, 4), (4, 2), (4, 5)];
+        let Err(Cycle(cycle)) = topological_sort(&[1, 2, 3, 4, 5], edges) else {
+            panic!("expected a cycle");
+        };
+
+        assert_eq!(cycle.len(), 3);
+        for (i, n) in cycle.iter().enumerate() {
+            let next = cycle[(i + 1) % cycle.len()];
+            assert!(edges.contains(&(*n, next)), "{:?}", cycle);
+        }
+
+        assert_eq!(topological_sort(&[7], [(7, 7)]), Err(Cycle(vec![7])));
+    }
+}
@@ -0,0 +1,182 @@
+use super::{
+    grid::Grid,
+    position::{Compass, Position},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WordMatch {
+    pub start: Position,
+    pub direction: Compass,
+}
+
+/// Every occurrence of `word` reading in a straight line in any of the eight directions.
+/// Palindromes are found twice, once each way.
+pub fn find_word(grid: &Grid<char>, word: &str) -> Vec<WordMatch> {
+    let word = word.chars().collect::<Vec<_>>();
+    let Some(first) = word.first() else {
+        return Vec::new();
+    };
+
+    let mut matches = Vec::new();
+    for (start, c) in grid.iter() {
+        if c != *first {
+            continue;
+        }
+
+        for direction in Compass::ALL8 {
+            let is_match = word
+                .iter()
+                .enumerate()
+                .all(|(i, c)| grid.is(start + direction * i as i32, *c));
+            if is_match {
+                matches.push(WordMatch { start, direction });
+            }
+        }
+    }
+
+    matches
+}
+
+/// A small 2D pattern to look for in a grid, where `.` matches anything.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stencil {
+    /// The non-wildcard cells, sorted so equal stencils compare equal.
+    cells: Vec<(Position, char)>,
+    n_rows: i32,
+    n_cols: i32,
+}
+
+impl Stencil {
+    pub const WILDCARD: char = '.';
+
+    pub fn new(pattern: &str) -> Self {
+        let lines = pattern.lines().collect::<Vec<_>>();
+        let cells = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(row, l)| {
+                l.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c != Stencil::WILDCARD)
+                    .map(move |(col, c)| (Position::from((row, col)), c))
+            })
+            .collect();
+
+        Stencil::from_cells(
+            cells,
+            lines.len() as i32,
+            lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32,
+        )
+    }
+
+    fn from_cells(mut cells: Vec<(Position, char)>, n_rows: i32, n_cols: i32) -> Self {
+        cells.sort_by_key(|(p, _)| (p.row, p.col));
+        Stencil {
+            cells,
+            n_rows,
+            n_cols,
+        }
+    }
+
+    /// A quarter turn clockwise.
+    pub fn rotated(&self) -> Self {
+        let cells = self
+            .cells
+            .iter()
+            .map(|(p, c)| (Position::new(p.col, self.n_rows - 1 - p.row), *c))
+            .collect();
+        Stencil::from_cells(cells, self.n_cols, self.n_rows)
+    }
+
+    /// Mirrored left to right.
+    pub fn reflected(&self) -> Self {
+        let cells = self
+            .cells
+            .iter()
+            .map(|(p, c)| (Position::new(p.row, self.n_cols - 1 - p.col), *c))
+            .collect();
+        Stencil::from_cells(cells, self.n_rows, self.n_cols)
+    }
+
+    /// Every distinct rotation and reflection, including the stencil itself.
+    pub fn variants(&self) -> Vec<Stencil> {
+        let mut variants: Vec<Stencil> = Vec::new();
+
+        for mut s in [self.clone(), self.reflected()] {
+            for _ in 0..4 {
+                if !variants.contains(&s) {
+                    variants.push(s.clone());
+                }
+                s = s.rotated();
+            }
+        }
+
+        variants
+    }
+
+    pub fn matches_at(&self, grid: &Grid<char>, top_left: Position) -> bool {
+        self.cells.iter().all(|(p, c)| grid.is(top_left + p, *c))
+    }
+
+    /// Top left corner of every match.
+    pub fn find(&self, grid: &Grid<char>) -> Vec<Position> {
+        (0..=grid.n_rows() - self.n_rows)
+            .flat_map(|row| {
+                (0..=grid.n_cols() - self.n_cols).map(move |col| Position::new(row, col))
+            })
+            .filter(|p| self.matches_at(grid, *p))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_word() {
+        let grid: Grid<char> = "XMAS\nMM..\nA.A.\nS..S".into();
+        let matches = find_word(&grid, "XMAS");
+
+        assert_eq!(matches.len(), 3);
+        for d in [Compass::E, Compass::S, Compass::SE] {
+            assert!(matches.contains(&WordMatch {
+                start: Position::new(0, 0),
+                direction: d
+            }));
+        }
+
+        assert_eq!(find_word(&grid, "SAMX").len(), 3);
+    }
+
+    #[test]
+    fn test_stencil_variants() {
+        let s = Stencil::new("M.S\n.A.\nM.S");
+        assert_eq!(s.variants().len(), 4);
+
+        let l = Stencil::new("X.\nXX");
+        assert_eq!(l.variants().len(), 4);
+        assert_eq!(l.rotated().rotated().rotated().rotated(), l);
+
+        let asymmetric = Stencil::new("AB\nC.");
+        assert_eq!(asymmetric.variants().len(), 8);
+
+        let wide = Stencil::new("AB");
+        assert_eq!(wide.rotated(), Stencil::new("A\nB"));
+    }
+
+    #[test]
+    fn test_stencil_find() {
+        let grid: Grid<char> = "MXS\nXAX\nMXS\nXAX".into();
+        let s = Stencil::new("M.S\n.A.\nM.S");
+
+        assert_eq!(s.find(&grid), vec![Position::new(0, 0)]);
+        assert_eq!(
+            s.variants()
+                .iter()
+                .map(|v| v.find(&grid).len())
+                .sum::<usize>(),
+            1
+        );
+    }
+}